@@ -0,0 +1,45 @@
+//! Benchmarks route lookup on a router sized like a large admin app (~400 routes).
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use velto::router::Router;
+
+fn build_router() -> Router<usize> {
+    let mut router = Router::new();
+    let mut id = 0;
+    for resource in 0..50 {
+        for path in [
+            format!("/admin/resource{}", resource),
+            format!("/admin/resource{}/new", resource),
+            format!("/admin/resource{}/:id", resource),
+            format!("/admin/resource{}/:id/edit", resource),
+            format!("/admin/resource{}/:id/history", resource),
+            format!("/api/v1/resource{}", resource),
+            format!("/api/v1/resource{}/:id", resource),
+            format!("/api/v1/resource{}/:id/files/*path", resource),
+        ] {
+            router.insert(&path, id).unwrap();
+            id += 1;
+        }
+    }
+    router
+}
+
+fn lookup(c: &mut Criterion) {
+    let router = build_router();
+
+    c.bench_function("static", |b| {
+        b.iter(|| router.at(black_box("/admin/resource42/new")))
+    });
+    c.bench_function("param", |b| {
+        b.iter(|| router.at(black_box("/admin/resource42/1234/edit")))
+    });
+    c.bench_function("wildcard", |b| {
+        b.iter(|| router.at(black_box("/api/v1/resource42/1234/files/a/b/c.txt")))
+    });
+    c.bench_function("miss", |b| {
+        b.iter(|| router.at(black_box("/admin/resource42/1234/missing")))
+    });
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
@@ -1,23 +1,40 @@
+use velto::form::FormData;
 use velto::prelude::*;
+use velto::validation::Validator;
 
-/// Unified handler for both GET and POST requests to `/signup`.
-/// - GET: renders the signup form
-/// - POST: parses form data and returns confirmation
-fn signup(req: &Request) -> Response {
-    match req.method().as_str() {
-        "POST" => {
-            let form = parse_form(std::str::from_utf8(req.body()).unwrap_or(""));
-            let username = form.get("username").map(|s| s.as_str()).unwrap_or("");
-            let password = form.get("password").map(|s| s.as_str()).unwrap_or("");
+/// Renders the signup form.
+fn signup_page(_req: &Request) -> Response {
+    render!("signup.html", {
+        "title" => "Sign Up"
+    })
+}
+
+/// Handles the submitted form, re-displaying it with error messages and
+/// the entered values when a field is invalid.
+fn signup(req: &Request) -> Result<Response, Error> {
+    let form = FormData::try_parse(req.body())?;
+    let result = Validator::new(&form)
+        .required("username")
+        .min_length("username", 3)
+        .max_length("username", 20)
+        .required("email")
+        .email("email")
+        .required("password")
+        .min_length("password", 8)
+        .matches("confirm", "password")
+        .sensitive("password")
+        .sensitive("confirm")
+        .validate();
 
-            let reply = format!("Signed up as: {}\nPassword: {}", username, password);
-            Response::from_string(reply).with_content_type("text/plain")
-        }
-        _ => {
-            render!("signup.html", {
-                "title" => "Sign Up"
-            })
-        }
+    match result {
+        Ok(()) => Ok(Response::from_string(format!(
+            "Signed up as: {}",
+            form.get("username").unwrap_or("")
+        ))),
+        Err(errors) => Ok(
+            render!("signup.html", { "title" => "Sign Up" }, errors.context())
+                .with_status_code(422),
+        ),
     }
 }
 
@@ -25,8 +42,8 @@ fn signup(req: &Request) -> Response {
 async fn main() -> std::io::Result<()> {
     let mut app = App::new();
 
-    // Register both GET and POST methods for "/signup" using one handler
-    route!(app, [GET, POST] "/signup" => signup);
+    route!(app, GET "/signup" => signup_page);
+    route!(app, POST "/signup" => signup);
 
     // Serve static files from the "static/" directory
     app.serve_static("static");
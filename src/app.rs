@@ -1,38 +1,85 @@
+use crate::error::{Error, ErrorHandlers, PanicDetails};
+use crate::handler::IntoHandler;
 use crate::http_method::Method;
-use crate::middleware::Middleware;
-use crate::router::{Handler, Response};
+use crate::middleware::{IntoMiddleware, Middleware, Next};
+use crate::router::{Handler, Response, Router};
+use crate::scope::{join_path, Scope};
+use crate::state::StateMap;
+use crate::template::{Environment, Value};
+use crate::url::{RouteNames, UrlError};
 use crate::util::mime_type_for;
 use crate::Request;
 use async_tiny::{Header, Server};
+use futures_util::FutureExt;
 use pathx::Normalize;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::ErrorKind;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
 
-/// Velto application instance. Manages routes, static directories, dev mode and middleware.
+/// The default number of requests `App::run` will dispatch concurrently.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 1024;
+
+/// Velto application instance. Manages routes, static directories, dev mode, middleware
+/// and shared state.
 pub struct App {
-    routes: Arc<Mutex<HashMap<String, HashMap<Method, Handler>>>>,
-    watch_dirs: Vec<String>,
+    routes: Router<HashMap<Method, Handler>>,
+    /// Static/watched directories, each with the URL prefix it is served under.
+    watch_dirs: Vec<(String, String)>,
     dev_mode: bool,
-    middlewares: Vec<Middleware>,
+    middlewares: Arc<Vec<Middleware>>,
+    max_in_flight: usize,
+    state: Arc<StateMap>,
+    names: Arc<RouteNames>,
+    environment: Arc<Environment>,
+    error_handlers: Arc<ErrorHandlers>,
 }
 
 impl App {
     /// Creates a new Velto app with no routes or static directories.
     pub fn new() -> Self {
         App {
-            routes: Arc::new(Mutex::new(HashMap::new())),
+            routes: Router::new(),
             watch_dirs: Vec::new(),
             dev_mode: false,
-            middlewares: Vec::new(),
+            middlewares: Arc::new(Vec::new()),
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            state: Arc::default(),
+            names: Arc::default(),
+            environment: Arc::default(),
+            error_handlers: Arc::default(),
         }
     }
 
+    /// Creates a new Velto app managing the given state value.
+    ///
+    /// Shorthand for `App::new()` followed by `manage(state)`.
+    pub fn with_state<T: Send + Sync + 'static>(state: T) -> Self {
+        let mut app = Self::new();
+        app.manage(state);
+        app
+    }
+
+    /// Registers a shared state value, such as a connection pool, config or cache.
+    ///
+    /// One value is kept per type; managing a second value of the same type
+    /// replaces the first. Handlers and middleware retrieve it with
+    /// `Request::state::<T>()`. Wrap the value in a `Mutex` or `RwLock` if
+    /// handlers need to mutate it.
+    pub fn manage<T: Send + Sync + 'static>(&mut self, value: T) {
+        Arc::make_mut(&mut self.state).insert(value);
+    }
+
     /// Registers a middleware function to be applied to all routes.
-    pub fn use_middleware(&mut self, mw: Middleware) {
-        self.middlewares.push(mw);
+    ///
+    /// Accepts either an `async fn(Request, Next) -> Response` or a synchronous
+    /// `fn(&Request, &dyn Fn(&Request) -> Response) -> Response`.
+    pub fn use_middleware<M>(&mut self, mw: impl IntoMiddleware<M>) {
+        Arc::make_mut(&mut self.middlewares).push(mw.into_middleware());
     }
 
     /// Enables development mode and triggers hot-reload behavior.
@@ -41,49 +88,297 @@ impl App {
         crate::set_dev_mode(true);
     }
 
+    /// Sets how many requests may be handled concurrently.
+    ///
+    /// Once the limit is reached, `run` dispatches no more requests until one
+    /// of the in-flight requests completes, so a burst of traffic waits
+    /// instead of spawning an unbounded number of handler tasks.
+    /// Defaults to [`DEFAULT_MAX_IN_FLIGHT`].
+    ///
+    /// This bounds handlers, not connections: the underlying server keeps
+    /// accepting connections and reading request bodies into memory while
+    /// dispatching waits. Limit body sizes, or put a proxy in front, to
+    /// bound memory under load.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is zero.
+    pub fn set_max_in_flight(&mut self, limit: usize) {
+        assert!(limit > 0, "max in-flight requests must be at least 1");
+        self.max_in_flight = limit;
+    }
+
+    /// Sets the size limits for `multipart/form-data` bodies parsed by the
+    /// [`Multipart`](crate::multipart::Multipart) extractor.
+    ///
+    /// Limits are managed like state, so a mounted app can set its own.
+    pub fn set_multipart_limits(&mut self, limits: crate::multipart::Limits) {
+        self.manage(limits);
+    }
+
     /// Returns true if development mode is active.
     pub fn is_dev_mode(&self) -> bool {
         self.dev_mode
     }
 
     /// Registers a route handler for a given method and path.
-    pub fn route(
-        &mut self,
-        method: Method,
-        path: &str,
-        handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
-    ) {
-        let mut routes = self.routes.lock().unwrap();
-        routes
-            .entry(path.to_string())
-            .or_default()
-            .insert(method, Box::new(handler));
+    ///
+    /// The handler may be a sync `fn(&Request) -> R` or an
+    /// `async fn(Request) -> R`, where `R` is a `Response` or anything else
+    /// implementing [`IntoResponse`](crate::response::IntoResponse), such as
+    /// `&str`, `(u16, String)` or `Result<Response, velto::Error>`.
+    /// Errors are passed to the hook set with [`App::on_error`].
+    ///
+    /// Paths may contain named parameters (`/users/:id`) and a trailing wildcard
+    /// (`/files/*rest`); captured values are available through `Request::param`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the method is already registered for this path, or if the path
+    /// conflicts with an existing route (e.g. `/users/:id` and `/users/:name`).
+    pub fn route<M>(&mut self, method: Method, path: &str, handler: impl IntoHandler<M>) {
+        self.add_route(method, path, handler.into_handler());
+    }
+
+    /// Sets the handler for error responses with the given status code.
+    ///
+    /// It replaces the built-in plain-text body for errors produced by the
+    /// framework, such as `404` when nothing matches, `405` when the path
+    /// exists but the method doesn't, or `500` when a handler panics.
+    /// Responses returned by your own handlers are left untouched.
+    ///
+    /// If the handler returns a `200` response (e.g. from `render!`), it is
+    /// sent with `status` instead. For `405` the `Allow` header is added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::test::TestRequest;
+    /// use velto::{App, Response};
+    ///
+    /// let mut app = App::new();
+    /// app.error_handler(404, |req: &velto::Request| {
+    ///     Response::from_string(format!("Nothing at {}", req.path()))
+    /// });
+    ///
+    /// let res = TestRequest::new("GET", "/missing").send(&app);
+    /// assert_eq!(res.status_code(), 404);
+    /// assert_eq!(res.body(), "Nothing at /missing");
+    /// ```
+    pub fn error_handler<M>(&mut self, status: u16, handler: impl IntoHandler<M>) {
+        Arc::make_mut(&mut self.error_handlers).insert(status, handler.into_handler());
+    }
+
+    /// Sets the handler for error responses with the given status code sent to
+    /// clients whose `Accept` header prefers JSON.
+    ///
+    /// Other clients keep using the handler set with [`App::error_handler`].
+    pub fn json_error_handler<M>(&mut self, status: u16, handler: impl IntoHandler<M>) {
+        Arc::make_mut(&mut self.error_handlers).insert_json(status, handler.into_handler());
+    }
+
+    /// Sets the hook that turns errors returned by handlers into responses.
+    ///
+    /// Without a hook, an error is answered by the error handler registered
+    /// for its status (see [`App::error_handler`]), or the built-in response.
+    /// Errors with a `5xx` status are logged either way.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::test::TestRequest;
+    /// use velto::{route, App, Error, Response};
+    ///
+    /// let mut app = App::new();
+    /// app.on_error(|err, _req| {
+    ///     Response::from_string(format!("Oops: {}", err)).with_status_code(err.status())
+    /// });
+    /// route!(app, "/admin" => |_req| -> Result<Response, Error> {
+    ///     Err(Error::forbidden("admins only"))
+    /// });
+    ///
+    /// let res = TestRequest::new("GET", "/admin").send(&app);
+    /// assert_eq!(res.status_code(), 403);
+    /// assert_eq!(res.body(), "Oops: admins only");
+    /// ```
+    pub fn on_error(&mut self, hook: impl Fn(Error, &Request) -> Response + Send + Sync + 'static) {
+        Arc::make_mut(&mut self.error_handlers).set_hook(Arc::new(hook));
+    }
+
+    /// Sets the handler for requests that match no route or static file.
+    ///
+    /// Shorthand for `error_handler(404, handler)`.
+    pub fn not_found<M>(&mut self, handler: impl IntoHandler<M>) {
+        self.error_handler(404, handler);
+    }
+
+    /// Names the route at `path` so URLs can be built with `url_for`.
+    ///
+    /// The `route!` macro can name a route as it is registered:
+    /// `route!(app, "/users/:id/edit" as "user_edit" => edit_user)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is already in use, or if no route is registered at
+    /// `path`.
+    pub fn name(&mut self, name: &str, path: &str) {
+        if !self.routes.iter().any(|(route, _)| route == path) {
+            panic!("{}", UrlError::UnknownPath(path.to_string()));
+        }
+        Arc::make_mut(&mut self.names)
+            .insert(name, path)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    /// Returns the named routes of this app.
+    pub fn route_names(&self) -> &RouteNames {
+        &self.names
+    }
+
+    /// Builds the URL of a named route, filling in its path parameters.
+    ///
+    /// See [`RouteNames::url_for`] for details.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        self.names.url_for(name, params)
+    }
+
+    /// Registers a template filter, used as `{{ value | name }}` or
+    /// `{{ value | name(arg, ...) }}` in templates rendered while handling
+    /// this app's requests or through [`App::environment`].
+    ///
+    /// The filter receives the value and the evaluated arguments. An error it
+    /// returns fails rendering with the template name and line. A filter
+    /// registered with the name of a built-in filter replaces it, except for
+    /// `safe`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::template::Value;
+    /// use velto::{App, Error};
+    ///
+    /// // `{{ price | currency }}` renders `$40.50` for a price of 40.5.
+    /// let mut app = App::new();
+    /// app.add_filter("currency", |value: &Value, _args: &[Value]| {
+    ///     let amount = value.as_f64().ok_or_else(|| Error::internal("not a number"))?;
+    ///     Ok(format!("${:.2}", amount).into())
+    /// });
+    /// ```
+    pub fn add_filter<F>(&mut self, name: &str, filter: F)
+    where
+        F: Fn(&Value, &[Value]) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.environment.add_filter(name, filter);
+    }
+
+    /// Returns the environment this app's templates are rendered in, which
+    /// holds its filters and cache of parsed templates.
+    ///
+    /// Templates rendered while the app handles a request use it
+    /// automatically. Elsewhere, such as in a task spawned from a handler or
+    /// a background job, render through it to use the app's filters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::template::{Context, Value};
+    /// use velto::App;
+    ///
+    /// let mut app = App::new();
+    /// app.add_filter("shout", |value: &Value, _args: &[Value]| {
+    ///     Ok(value.to_string().to_uppercase().into())
+    /// });
+    ///
+    /// let env = app.environment();
+    /// let job = std::thread::spawn(move || env.render("index.html", &Context::new()));
+    /// assert!(job.join().unwrap().is_ok());
+    /// ```
+    pub fn environment(&self) -> Arc<Environment> {
+        self.environment.clone()
+    }
+
+    /// Replaces the app's template environment, for example to load
+    /// templates from another directory.
+    ///
+    /// Filters added to the app before are dropped with the old environment,
+    /// so call this before [`App::add_filter`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::template::Environment;
+    /// use velto::App;
+    ///
+    /// let mut app = App::new();
+    /// app.set_environment(Environment::with_root("site/templates"));
+    /// assert_eq!(app.environment().root(), std::path::Path::new("site/templates"));
+    /// ```
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = Arc::new(environment);
+    }
+
+    /// Checks that every `url_for` call in the templates under `dir` refers
+    /// to a named route and supplies all of its parameters.
+    ///
+    /// `run` performs this check on the root of the app's template
+    /// environment (`templates/` by default) at startup.
+    pub fn check_templates(&self, dir: &str) -> Result<(), UrlError> {
+        crate::template::check_url_for(Path::new(dir), &self.names)
     }
 
     /// Returns all registered routes
-    pub fn get_routes(
-        &self,
-    ) -> std::sync::MutexGuard<'_, HashMap<String, HashMap<Method, Handler>>> {
-        self.routes.lock().unwrap()
+    pub fn get_routes(&self) -> &Router<HashMap<Method, Handler>> {
+        &self.routes
     }
 
     /// Registers the same handler for multiple methods at a single path.
-    pub fn route_all(
-        &mut self,
-        methods: &[Method],
-        path: &str,
-        handler: impl Fn(&Request) -> Response + Send + Sync + 'static + Clone,
-    ) {
-        let mut routes = self.routes.lock().unwrap();
-        let method_map = routes.entry(path.to_string()).or_default();
+    pub fn route_all<M>(&mut self, methods: &[Method], path: &str, handler: impl IntoHandler<M>) {
+        let handler = handler.into_handler();
         for method in methods {
-            method_map.insert(method.clone(), Box::new(handler.clone()));
+            self.add_route(method.clone(), path, handler.clone());
         }
     }
 
+    /// Groups routes under a path prefix with their own middleware stack.
+    ///
+    /// Scopes can be nested; see [`Scope`] for details.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::{route, App, Response};
+    ///
+    /// let mut app = App::new();
+    /// app.scope("/api", |api| {
+    ///     api.scope("/v1", |v1| {
+    ///         route!(v1, "/status" => |_req| Response::from_string("ok"));
+    ///     });
+    /// });
+    /// assert!(app.get_routes().at("/api/v1/status").is_some());
+    /// ```
+    pub fn scope(&mut self, prefix: &str, build: impl FnOnce(&mut Scope)) {
+        let mut scope = Scope::new(prefix);
+        build(&mut scope);
+        let names = scope.take_names();
+        for (method, path, handler) in scope.into_routes() {
+            self.add_route(method, &path, handler);
+        }
+        for (name, path) in names {
+            self.name(&name, &path);
+        }
+    }
+
+    fn add_route(&mut self, method: Method, path: &str, handler: Handler) {
+        let method_map = self.routes.entry(path).unwrap_or_else(|e| panic!("{}", e));
+        if method_map.contains_key(&method) {
+            panic!("route [{:?}] {} is already registered", method, path);
+        }
+        method_map.insert(method, handler);
+    }
+
     /// Adds a directory to serve static files from.
     pub fn serve_static(&mut self, dir: &str) {
-        self.watch_dirs.push(dir.to_string());
+        self.watch_dirs.push((String::new(), dir.to_string()));
     }
 
     /// Alias for `serve_static`.
@@ -93,98 +388,359 @@ impl App {
 
     /// Adds a directory to be watched for changes in dev mode.
     pub fn watch_path(&mut self, dir: &str) {
-        self.watch_dirs.push(dir.to_string());
+        self.watch_dirs.push((String::new(), dir.to_string()));
+    }
+
+    /// Mounts another app under a path prefix.
+    ///
+    /// The sub-app's routes, route names and static directories are served under `prefix`.
+    /// Its template filters are added to this app's, which win on a name clash.
+    ///
+    /// The rest of the sub-app's configuration is dropped: its error handlers
+    /// and error hook (errors from its routes go to this app's), and its
+    /// template environment's root and cache (its routes render templates
+    /// from this app's environment). Register those on the parent instead.
+    ///
+    /// Its middleware only runs for its own routes (inside this app's
+    /// middleware), and state it manages is visible to its own handlers and
+    /// middleware, taking precedence over state of the same type managed here.
+    /// This lets feature areas be built and tested as independent apps and
+    /// composed in `main`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a mounted route conflicts with an existing one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::test::TestRequest;
+    /// use velto::{route, App, Response};
+    ///
+    /// fn billing() -> App {
+    ///     let mut app = App::new();
+    ///     route!(app, "/invoices/:id" => |req| {
+    ///         Response::from_string(format!("Invoice {}", req.param("id").unwrap_or("")))
+    ///     });
+    ///     app
+    /// }
+    ///
+    /// let mut app = App::new();
+    /// app.mount("/billing", billing());
+    ///
+    /// let res = TestRequest::new("GET", "/billing/invoices/7").send(&app);
+    /// assert_eq!(res.body(), "Invoice 7");
+    /// ```
+    pub fn mount(&mut self, prefix: &str, app: App) {
+        let state = app.state;
+        let middlewares = app.middlewares;
+        for (path, methods) in app.routes.iter() {
+            for (method, handler) in methods {
+                let handler = crate::middleware::wrap(middlewares.clone(), handler.clone());
+                let handler = with_state_layer(state.clone(), handler);
+                self.add_route(method.clone(), &join_path(prefix, path), handler);
+            }
+        }
+        for (name, path) in app.names.iter() {
+            self.name(name, &join_path(prefix, path));
+        }
+        self.environment.merge_filters(&app.environment);
+        for (dir_prefix, dir) in app.watch_dirs {
+            self.watch_dirs.push((join_path(prefix, &dir_prefix), dir));
+        }
     }
 
     /// Starts the HTTP server and handles incoming requests.
     /// In dev mode, also launches the LiveReload system.
-    pub async fn run(&self, addr: &str) -> std::io::Result<()> {
+    ///
+    /// Each request is dispatched on its own tokio task, so a slow handler does
+    /// not hold up other clients. The app is shared read-only between tasks.
+    ///
+    /// On first use, adds a panic hook that records where handler panics
+    /// happen for the `500` response. It chains to the hook already set, which
+    /// still runs for every panic.
+    ///
+    /// Fails before binding if a template refers to an unknown route name
+    /// (see [`App::check_templates`]).
+    pub async fn run(self, addr: &str) -> std::io::Result<()> {
+        crate::template::check_url_for(self.environment.root(), &self.names)
+            .map_err(std::io::Error::other)?;
+
+        crate::error::install_panic_hook();
         let mut server = Server::http(addr, true).await?;
         println!("🚀 Running on http://{}", addr);
 
         if !self.watch_dirs.is_empty() {
             println!("📁 Serving static files from:");
-            for dir in &self.watch_dirs {
-                println!("   • {}", dir);
+            for (prefix, dir) in &self.watch_dirs {
+                match prefix.as_str() {
+                    "" => println!("   • {}", dir),
+                    _ => println!("   • {} → {}", dir, prefix),
+                }
             }
         }
 
         println!("🔗 Registered routes:");
-        for (path, method_map) in self.routes.lock().unwrap().iter() {
+        for (path, method_map) in self.routes.iter() {
             for method in method_map.keys() {
-                println!("   • [{:?}] {}", method, path);
+                println!("   • [{}] {}", method, path);
             }
         }
 
         // Start LiveReload after printing startup info
         if self.dev_mode {
             let (tx, _) = tokio::sync::broadcast::channel(100);
-            let mut dirs = self.watch_dirs.clone();
-            if !dirs.contains(&"templates".to_string()) {
-                dirs.push("templates".to_string());
+            let mut dirs: Vec<String> = self.watch_dirs.iter().map(|(_, d)| d.clone()).collect();
+            let templates = self.environment.root().display().to_string();
+            if !dirs.contains(&templates) {
+                dirs.push(templates);
             }
+            let environment = self.environment.clone();
             tokio::spawn(async move {
-                crate::reload::start(tx, dirs).await;
+                crate::reload::start(tx, dirs, environment).await;
             });
         }
 
-        // Handle incoming requests
+        // Handle incoming requests, each on its own task
+        let app = Arc::new(self);
+        let in_flight = Arc::new(Semaphore::new(app.max_in_flight));
         while let Some(request) = server.next().await {
-            let method = Method::from_hyper(request.method());
-            let url = request.url().to_string();
-            let routes = self.routes.lock().unwrap();
+            let permit = in_flight
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("in-flight semaphore is never closed");
+            let app = app.clone();
+            tokio::spawn(async move {
+                let response = app.handle(Request::from_tiny(&request)).await;
+                let _ = request.respond(response);
+                drop(permit);
+            });
+        }
 
-            let mut response = None;
+        Ok(())
+    }
+
+    /// Dispatches a request through routing, middleware and static file lookup.
+    ///
+    /// This is shared by `run` and `TestRequest::send` so tests resolve routes
+    /// exactly like the running server does. Responses to HEAD requests keep
+    /// their body and headers: the server sends only the headers, with the
+    /// body's Content-Length, and `TestRequest` strips the body itself.
+    ///
+    /// A panic in a handler or middleware is caught and turned into a `500`
+    /// response; see [`App::error_handler`] and [`PanicDetails`].
+    pub(crate) async fn handle(&self, request: Request) -> Response {
+        let method = Method::from_hyper(request.method());
+        let original = request.clone();
+        let response = crate::error::dispatching(self.dispatch(&method, request));
+        let response = AssertUnwindSafe(response).catch_unwind();
+        match self.with_template_env(response).await {
+            Ok(response) => response,
+            Err(payload) => {
+                let details = crate::error::panic_details(&*payload);
+                let response = self.panic_response(&method, original, details);
+                self.with_template_env(response).await
+            }
+        }
+    }
 
-            if let Some(method_map) = routes.get(&url) {
-                if let Some(handler) = method_map.get(&method) {
-                    let mut wrapped: Box<dyn Fn(&Request) -> Response + Send + Sync> =
-                        Box::new(|req| handler(req));
+    /// Runs `fut` with this app's route names and template environment
+    /// used for templates rendered inside it.
+    async fn with_template_env<F: std::future::Future>(&self, fut: F) -> F::Output {
+        let fut = crate::template::environment::scope(self.environment.clone(), fut);
+        crate::url::with_names(self.names.clone(), fut).await
+    }
 
-                    for mw in self.middlewares.iter().rev() {
-                        let next = wrapped;
-                        wrapped = Box::new(move |req| mw(req, &next));
-                    }
+    /// Logs a caught panic and builds the `500` response for it.
+    ///
+    /// In dev mode the panic details are shown in the default response and
+    /// attached to the request passed to a custom `500` handler.
+    async fn panic_response(
+        &self,
+        method: &Method,
+        request: Request,
+        details: PanicDetails,
+    ) -> Response {
+        let route = self
+            .routes
+            .at(request.path())
+            .map_or(request.path(), |matched| matched.route);
+        eprintln!(
+            "💥 [{}] {} panicked while handling {}: {}",
+            method,
+            route,
+            request.url(),
+            details.message
+        );
 
-                    response = Some(wrapped(&request));
-                }
+        let mut fallback = crate::error::default_response(500);
+        let request = request
+            .with_state(self.state.clone())
+            .with_route_names(self.names.clone());
+        let request = match crate::is_dev_mode() {
+            true => {
+                fallback = Response::from_string(format!("{}\n\n{}", fallback.body(), details))
+                    .with_status_code(500);
+                let mut layer = StateMap::new();
+                layer.insert(details);
+                request.with_state_layer(Arc::new(layer))
             }
+            false => request,
+        };
 
-            if response.is_none() {
-                for dir in &self.watch_dirs {
-                    let raw_path = PathBuf::from(dir).join(url.trim_start_matches('/'));
-
-                    match raw_path.normalize() {
-                        Ok(normalized_path) => {
-                            if let Ok(content) = fs::read(&normalized_path) {
-                                let mime = mime_type_for(&normalized_path);
-                                response = Some(Response::from_data(content).with_header(
-                                    Header::from_str(&format!("Content-Type: {}", mime)).unwrap(),
-                                ));
-                                break;
-                            } else if self.dev_mode {
-                                println!("⚠️ Static file not found: {}", normalized_path.display());
-                            }
-                        }
-                        Err(e) => {
-                            if self.dev_mode {
-                                println!("⚠️ Failed to normalize path: {e}");
-                            }
-                        }
-                    }
-                }
+        let response = self.error_handlers.respond_or(500, request, fallback);
+        AssertUnwindSafe(response)
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|_| crate::error::default_response(500))
+    }
 
-                if response.is_none() {
-                    response = Some(Response::from_string("404 Not Found").with_status_code(404));
-                }
+    async fn dispatch(&self, method: &Method, request: Request) -> Response {
+        let request = request
+            .with_state(self.state.clone())
+            .with_route_names(self.names.clone())
+            .with_error_handlers(self.error_handlers.clone());
+        let path = request.path().to_string();
+
+        let found = self.routes.at(&path).map(|matched| {
+            let methods = matched.value;
+            let handler = methods
+                .get(method)
+                .or_else(|| match method {
+                    Method::HEAD => methods.get(&Method::GET),
+                    _ => None,
+                })
+                .cloned()
+                .unwrap_or_else(|| auto_handler(method, methods, &self.error_handlers));
+            (handler, matched.params)
+        });
+
+        if let Some((handler, params)) = found {
+            let next = Next::new(self.middlewares.clone(), handler);
+            return next.run(request.with_params(params)).await;
+        }
+
+        match self.serve_file(&path) {
+            Ok(Some(response)) => response,
+            Ok(None) => self.error_handlers.respond(404, request).await,
+            Err(error) => self.error_handlers.handle(error, request).await,
+        }
+    }
+
+    /// Looks up `path` in the static directories.
+    ///
+    /// Returns `Ok(None)` if no directory has the file, and an error if the
+    /// path escapes its directory (`403`) or the file cannot be read.
+    fn serve_file(&self, path: &str) -> Result<Option<Response>, Error> {
+        for (prefix, dir) in &self.watch_dirs {
+            let Some(rest) = path.strip_prefix(prefix.as_str()) else {
+                continue;
+            };
+            if !prefix.is_empty() && !rest.starts_with('/') {
+                continue;
             }
+            let root = PathBuf::from(dir);
+            let raw_path = root.join(rest.trim_start_matches('/'));
 
-            if let Some(resp) = response {
-                let _ = request.respond(resp);
+            let normalized_path = match (raw_path.normalize(), root.normalize()) {
+                (Ok(path), Ok(root)) if path.starts_with(&root) => path,
+                _ => return Err(Error::forbidden(format!("path escapes {}: {}", dir, path))),
+            };
+            match fs::read(&normalized_path) {
+                Ok(content) => {
+                    let mime = mime_type_for(&normalized_path);
+                    return Ok(Some(Response::from_data(content).with_header(
+                        Header::from_str(&format!("Content-Type: {}", mime)).unwrap(),
+                    )));
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::IsADirectory) => {
+                    if self.dev_mode {
+                        println!("⚠️ Static file not found: {}", normalized_path.display());
+                    }
+                }
+                Err(e) => return Err(e.into()),
             }
         }
+        Ok(None)
+    }
+}
 
-        Ok(())
+/// Wraps a mounted app's handler so its managed state is visible to it.
+fn with_state_layer(state: Arc<StateMap>, handler: Handler) -> Handler {
+    Arc::new(move |req| handler(req.with_state_layer(state.clone())))
+}
+
+/// Builds the `Allow` header value for a path: its registered methods, plus
+/// HEAD and OPTIONS which are answered automatically.
+fn allow_header(methods: &HashMap<Method, Handler>) -> String {
+    let mut allowed: Vec<Method> = methods.keys().cloned().collect();
+    if methods.contains_key(&Method::GET) && !methods.contains_key(&Method::HEAD) {
+        allowed.push(Method::HEAD);
+    }
+    if !methods.contains_key(&Method::OPTIONS) {
+        allowed.push(Method::OPTIONS);
+    }
+    let order = [
+        Method::GET,
+        Method::HEAD,
+        Method::POST,
+        Method::PUT,
+        Method::DELETE,
+        Method::PATCH,
+        Method::OPTIONS,
+        Method::TRACE,
+        Method::CONNECT,
+    ];
+    allowed.sort_by_key(|m| {
+        let rank = order.iter().position(|o| o == m).unwrap_or(order.len());
+        (rank, m.to_string())
+    });
+    allowed
+        .iter()
+        .map(Method::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The handler used when a path matches but the method has no handler:
+/// an automatic OPTIONS reply, or 405 Method Not Allowed.
+fn auto_handler(
+    method: &Method,
+    methods: &HashMap<Method, Handler>,
+    errors: &Arc<ErrorHandlers>,
+) -> Handler {
+    let allow = format!("Allow: {}", allow_header(methods));
+    if *method == Method::OPTIONS {
+        let response = Response::empty(204).with_header(Header::from_str(&allow).unwrap());
+        return (move |_req: &Request| response.clone()).into_handler();
+    }
+    let errors = errors.clone();
+    (move |req: Request| {
+        let errors = errors.clone();
+        let allow = Header::from_str(&allow).unwrap();
+        async move { errors.respond(405, req).await.with_header(allow) }
+    })
+    .into_handler()
+}
+
+/// Strips the body from a response to a HEAD request, keeping its status,
+/// its headers and the Content-Length the body would have had.
+pub(crate) fn without_body(response: Response) -> Response {
+    let length = response.body().len();
+    let stripped = response.headers().iter().fold(
+        Response::empty(response.status_code()),
+        |res, (name, value)| res.with_header(Header(name.clone(), value.clone())),
+    );
+    match response
+        .headers()
+        .contains_key(http::header::CONTENT_LENGTH)
+    {
+        true => stripped,
+        false => stripped.with_header(Header(
+            http::header::CONTENT_LENGTH,
+            http::HeaderValue::from(length),
+        )),
     }
 }
 
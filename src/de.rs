@@ -0,0 +1,330 @@
+//! A serde deserializer over string key/value pairs, used for path
+//! parameters, query strings and URL-encoded forms.
+
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, Error as _, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use std::collections::HashMap;
+
+/// Deserializes `T` from key/value pairs.
+///
+/// Structs and maps take one entry per key; repeated keys can fill a `Vec`
+/// field, and otherwise the last value wins. Keys in bracket notation nest:
+/// `user[address][city]` fills the `city` field of the `address` field of
+/// `user`, `items[0][name]` fills a `Vec` of structs, and `tags[]` is the same
+/// as `tags`. Sequences and tuples take the values in order, and a single
+/// value can be deserialized directly.
+pub(crate) fn from_pairs<'a, T, I>(pairs: I) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let mut root = Node::default();
+    for (key, value) in pairs {
+        root.insert(key_path(key)?, value);
+    }
+    T::deserialize(root)
+}
+
+/// How many segments a key in bracket notation may have.
+const MAX_DEPTH: usize = 32;
+
+/// Splits a key in bracket notation into its segments, e.g.
+/// `user[address][city]` into `user`, `address` and `city`. Empty brackets
+/// are dropped, and keys that are not well-formed are kept whole. Keys with
+/// more than [`MAX_DEPTH`] segments are rejected.
+fn key_path(key: &str) -> Result<Vec<&str>, Error> {
+    let Some(open) = key.find('[').filter(|&i| i > 0) else {
+        return Ok(vec![key]);
+    };
+    let mut path = vec![&key[..open]];
+    let mut rest = &key[open..];
+    while let Some(bracketed) = rest.strip_prefix('[') {
+        let Some(close) = bracketed.find(']') else {
+            return Ok(vec![key]);
+        };
+        let segment = &bracketed[..close];
+        if segment.contains('[') {
+            return Ok(vec![key]);
+        }
+        if !segment.is_empty() {
+            path.push(segment);
+        }
+        if path.len() > MAX_DEPTH {
+            return Err(Error::custom(format!(
+                "key `{}` is nested more than {} levels deep",
+                &key[..open],
+                MAX_DEPTH
+            )));
+        }
+        rest = &bracketed[close + 1..];
+    }
+    Ok(match rest.is_empty() {
+        true => path,
+        false => vec![key],
+    })
+}
+
+/// A key in the tree of pairs: the values given for the key itself, and the
+/// nested keys below it.
+#[derive(Default)]
+struct Node<'a> {
+    values: Vec<&'a str>,
+    children: Vec<(&'a str, Node<'a>)>,
+    /// The position of each nested key in `children`, so that inserting
+    /// many keys does not search the list for each one.
+    index: HashMap<&'a str, usize>,
+}
+
+impl<'a> Node<'a> {
+    fn insert(&mut self, path: Vec<&'a str>, value: &'a str) {
+        let mut node = self;
+        for segment in path {
+            let next = node.children.len();
+            let index = *node.index.entry(segment).or_insert(next);
+            if index == next {
+                node.children.push((segment, Node::default()));
+            }
+            node = &mut node.children[index].1;
+        }
+        node.values.push(value);
+    }
+
+    /// The value used when a single value is expected: the last one given,
+    /// or the only nested key's.
+    fn single(self) -> Result<Value<'a>, Error> {
+        if let Some(value) = self.values.last() {
+            return Ok(Value(value));
+        }
+        match <[_; 1]>::try_from(self.children) {
+            Ok([(_, child)]) => child.single(),
+            Err(children) => Err(Error::custom(format!(
+                "expected 1 value, found {}",
+                children.len()
+            ))),
+        }
+    }
+
+    /// The nested keys in order, sorted by index if they are all numbers
+    /// (as in `items[1]`, `items[0]`).
+    fn elements(mut self) -> Vec<Node<'a>> {
+        if self
+            .children
+            .iter()
+            .all(|(k, _)| k.parse::<usize>().is_ok())
+        {
+            self.children
+                .sort_by_key(|(k, _)| k.parse::<usize>().unwrap_or_default());
+        }
+        self.children.into_iter().map(|(_, node)| node).collect()
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Node<'_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! forward_to_single {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Node<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.children.is_empty() {
+            true => self.single()?.deserialize_any(visitor),
+            false => self.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(MapDeserializer::new(self.children.into_iter()))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    /// Nested keys become the elements, otherwise the key's own values do.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.children.is_empty() {
+            true => visitor.visit_seq(SeqDeserializer::new(self.values.into_iter().map(Value))),
+            false => visitor.visit_seq(SeqDeserializer::new(self.elements().into_iter())),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    /// A key with nested keys is always present; otherwise see [`Value`].
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match (self.values.is_empty(), self.children.is_empty()) {
+            (true, true) => visitor.visit_none(),
+            (_, false) => visitor.visit_some(self),
+            (false, true) => self.single()?.deserialize_option(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_single! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_identifier
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf ignored_any
+    }
+}
+
+/// A single string value, parsed into whatever type is requested.
+struct Value<'a>(&'a str);
+
+impl<'de> IntoDeserializer<'de, Error> for Value<'_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.trim().parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(Error::invalid_value(de::Unexpected::Str(self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_str(self.0)
+    }
+
+    /// Accepts `true`/`false` as well as the `on` sent by HTML checkboxes.
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            "true" | "on" | "1" => visitor.visit_bool(true),
+            "false" | "off" | "0" => visitor.visit_bool(false),
+            other => Err(Error::invalid_value(de::Unexpected::Str(other), &visitor)),
+        }
+    }
+
+    parse_value! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    /// An empty value, such as an empty text input, is `None`.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            "" => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(SeqDeserializer::new(std::iter::once(self)))
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
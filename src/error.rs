@@ -0,0 +1,387 @@
+use crate::router::Handler;
+use crate::{Request, Response};
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::panic;
+use std::sync::{Arc, Once};
+
+/// An error returned by a handler, carrying the HTTP status to respond with.
+///
+/// Handlers may return `Result<T, E>` where `E: Into<velto::Error>`, so `?`
+/// works on any `std::error::Error` (which becomes a `500`). Use the
+/// constructors to pick another status. Errors are turned into responses by
+/// the app's error hook (see `App::on_error`), which by default uses the
+/// error handler registered for the status.
+///
+/// Like `anyhow::Error`, this type does not itself implement
+/// `std::error::Error`, so that the blanket conversion is possible.
+///
+/// # Example
+///
+/// ```
+/// use velto::test::TestRequest;
+/// use velto::{route, App, Error, Response};
+///
+/// let mut app = App::new();
+/// route!(app, "/users/:id" => |req| -> Result<Response, Error> {
+///     let id: u32 = req.param("id").unwrap_or("").parse()?;
+///     match id {
+///         1 => Ok(Response::from_string("alice")),
+///         _ => Err(Error::not_found(format!("no user {}", id))),
+///     }
+/// });
+///
+/// assert_eq!(TestRequest::new("GET", "/users/1").send(&app).body(), "alice");
+/// assert_eq!(TestRequest::new("GET", "/users/2").send(&app).status_code(), 404);
+/// assert_eq!(TestRequest::new("GET", "/users/x").send(&app).status_code(), 500);
+/// ```
+pub struct Error {
+    status: u16,
+    message: String,
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+impl Error {
+    /// Creates an error with the given status code and message.
+    pub fn new(status: u16, message: impl Into<String>) -> Self {
+        Error {
+            status,
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// A `400 Bad Request` error.
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(400, message)
+    }
+
+    /// A `403 Forbidden` error.
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(403, message)
+    }
+
+    /// A `404 Not Found` error.
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(404, message)
+    }
+
+    /// A `500 Internal Server Error`.
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(500, message)
+    }
+
+    /// Returns the error with a different status code.
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Returns the HTTP status code to respond with.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the underlying error, if this was converted from one.
+    pub fn source(&self) -> Option<&(dyn std::error::Error + Send + Sync + 'static)> {
+        self.source.as_deref()
+    }
+}
+
+impl<E: std::error::Error + Send + Sync + 'static> From<E> for Error {
+    fn from(error: E) -> Self {
+        Error {
+            status: 500,
+            message: error.to_string(),
+            source: Some(Box::new(error)),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Error")
+            .field("status", &self.status)
+            .field("message", &self.message)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+/// A function that maps handler errors to responses, set with `App::on_error`.
+pub(crate) type ErrorHook = Arc<dyn Fn(Error, &Request) -> Response + Send + Sync>;
+
+/// Handlers for error responses generated by the framework, keyed by status code,
+/// and the hook for errors returned by handlers.
+///
+/// Registered through `App::error_handler`, `App::json_error_handler`,
+/// `App::not_found` and `App::on_error`.
+#[derive(Clone, Default)]
+pub(crate) struct ErrorHandlers {
+    any: HashMap<u16, Handler>,
+    json: HashMap<u16, Handler>,
+    hook: Option<ErrorHook>,
+}
+
+impl fmt::Debug for ErrorHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ErrorHandlers")
+            .field("any", &self.any.keys())
+            .field("json", &self.json.keys())
+            .field("hook", &self.hook.is_some())
+            .finish()
+    }
+}
+
+impl ErrorHandlers {
+    /// Sets the hook for errors returned by handlers.
+    pub(crate) fn set_hook(&mut self, hook: ErrorHook) {
+        self.hook = Some(hook);
+    }
+
+    /// Turns an error returned by a handler into a response.
+    ///
+    /// Uses the hook if one is set, and otherwise the error handler registered
+    /// for the error's status. Server errors are logged; in dev mode the
+    /// error message is added to the built-in response. With the `json`
+    /// feature, clients that prefer JSON get a [`json_response`] instead.
+    pub(crate) async fn handle(&self, error: Error, req: Request) -> Response {
+        if error.status >= 500 {
+            eprintln!("❌ [{}] {} failed: {}", req.method(), req.url(), error);
+        }
+        if let Some(hook) = &self.hook {
+            return hook(error, &req);
+        }
+        let status = error.status;
+        #[cfg(feature = "json")]
+        if wants_json(&req) {
+            let fallback = json_response(&error);
+            return self.respond_or(status, req, fallback).await;
+        }
+        let mut fallback = default_response(status);
+        if crate::is_dev_mode() {
+            fallback = Response::from_string(format!("{}\n\n{}", fallback.body(), error))
+                .with_status_code(status);
+        }
+        self.respond_or(status, req, fallback).await
+    }
+
+    /// Sets the handler used for `status` when the client does not ask for JSON
+    /// (or when no JSON handler is registered).
+    pub(crate) fn insert(&mut self, status: u16, handler: Handler) {
+        self.any.insert(status, handler);
+    }
+
+    /// Sets the handler used for `status` when the client prefers JSON.
+    pub(crate) fn insert_json(&mut self, status: u16, handler: Handler) {
+        self.json.insert(status, handler);
+    }
+
+    /// Produces the response for an error `status`.
+    ///
+    /// Falls back to a plain-text `"<status> <reason>"` body when no handler
+    /// is registered. A handler's response keeps its own status unless it is
+    /// `200`, in which case it is given the error status.
+    pub(crate) async fn respond(&self, status: u16, req: Request) -> Response {
+        self.respond_or(status, req, default_response(status)).await
+    }
+
+    /// Like [`respond`](Self::respond), but with the given fallback response
+    /// when no handler is registered for `status`.
+    pub(crate) async fn respond_or(
+        &self,
+        status: u16,
+        req: Request,
+        fallback: Response,
+    ) -> Response {
+        let handler = match wants_json(&req) {
+            true => self.json.get(&status).or_else(|| self.any.get(&status)),
+            false => self.any.get(&status),
+        };
+        match handler {
+            Some(handler) => {
+                let response = handler(req).await;
+                match response.status_code() {
+                    200 => response.with_status_code(status),
+                    _ => response,
+                }
+            }
+            None => fallback,
+        }
+    }
+}
+
+/// The built-in error response, e.g. `404 Not Found`.
+pub(crate) fn default_response(status: u16) -> Response {
+    let reason = http::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or("Error");
+    Response::from_string(format!("{} {}", status, reason)).with_status_code(status)
+}
+
+/// The built-in JSON error response: `{"status": 400, "error": "..."}`.
+///
+/// Client errors (`4xx`) carry the error's message, such as where a JSON body
+/// failed to parse. Server errors only carry the status reason, unless dev
+/// mode is on, so internal details do not leak.
+#[cfg(feature = "json")]
+pub(crate) fn json_response(error: &Error) -> Response {
+    let message = match error.status < 500 || crate::is_dev_mode() {
+        true => error.message.clone(),
+        false => http::StatusCode::from_u16(error.status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .unwrap_or("Error")
+            .to_string(),
+    };
+    let body = serde_json::json!({ "status": error.status, "error": message });
+    Response::from_string(body.to_string())
+        .with_content_type("application/json")
+        .with_status_code(error.status)
+}
+
+/// Returns true if the request's `Accept` header prefers JSON over HTML.
+pub(crate) fn wants_json(req: &Request) -> bool {
+    let Some(accept) = req
+        .headers()
+        .get(http::header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    match (accept.find("json"), accept.find("text/html")) {
+        (Some(json), Some(html)) => json < html,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Details of a handler panic, available to the `500` error handler in dev mode.
+///
+/// Retrieve them with `req.state::<PanicDetails>()`; outside dev mode they are
+/// never attached, so panic messages cannot leak into production error pages.
+#[derive(Debug, Clone)]
+pub struct PanicDetails {
+    /// The panic message, e.g. `called Option::unwrap() on a None value`.
+    pub message: String,
+    /// Where the panic happened, as `file:line:column`.
+    pub location: Option<String>,
+    /// The backtrace captured at the panic site.
+    pub backtrace: Option<String>,
+}
+
+impl fmt::Display for PanicDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "panicked")?;
+        if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(backtrace) = &self.backtrace {
+            write!(f, "\n\n{}", backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+/// Location and backtrace of a panic, as recorded by the panic hook.
+pub(crate) type PanicRecord = Option<(String, Option<String>)>;
+
+thread_local! {
+    /// The last panic on this thread, recorded by the panic hook.
+    static LAST_PANIC: RefCell<PanicRecord> = const { RefCell::new(None) };
+
+    /// Whether this thread is running a handler or middleware for the
+    /// dispatcher, whose panics are caught and reported.
+    static DISPATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks the current thread as dispatching until dropped.
+pub(crate) struct DispatchGuard(bool);
+
+impl DispatchGuard {
+    pub(crate) fn enter() -> Self {
+        DispatchGuard(DISPATCHING.with(|d| d.replace(true)))
+    }
+}
+
+impl Drop for DispatchGuard {
+    fn drop(&mut self) {
+        DISPATCHING.with(|d| d.set(self.0));
+    }
+}
+
+/// Polls `fut` with the current thread marked as dispatching, so the panic
+/// hook records panics raised while it runs.
+pub(crate) async fn dispatching<F: Future>(fut: F) -> F::Output {
+    let mut fut = std::pin::pin!(fut);
+    std::future::poll_fn(|cx| {
+        let _guard = DispatchGuard::enter();
+        fut.as_mut().poll(cx)
+    })
+    .await
+}
+
+/// Takes the record of the last panic on this thread, so it can be moved to
+/// the thread that resumes the unwind.
+pub(crate) fn take_panic_record() -> PanicRecord {
+    LAST_PANIC.with(|last| last.borrow_mut().take())
+}
+
+/// Restores a record taken with [`take_panic_record`] on the current thread.
+pub(crate) fn restore_panic_record(record: PanicRecord) {
+    LAST_PANIC.with(|last| *last.borrow_mut() = record);
+}
+
+/// Installs (once) a panic hook that records where panics in handlers and
+/// middleware happen, so they can be reported with the 500 response. Other
+/// panics are left alone, and the previous hook runs for every panic.
+pub(crate) fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if DISPATCHING.with(Cell::get) {
+                let location = info.location().map(ToString::to_string).unwrap_or_default();
+                let backtrace =
+                    crate::is_dev_mode().then(|| Backtrace::force_capture().to_string());
+                LAST_PANIC.with(|last| *last.borrow_mut() = Some((location, backtrace)));
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Builds the details of a caught panic from its payload and the hook's record.
+pub(crate) fn panic_details(payload: &(dyn Any + Send)) -> PanicDetails {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string());
+    let (location, backtrace) = take_panic_record()
+        .map_or((None, None), |(location, backtrace)| {
+            (Some(location).filter(|l| !l.is_empty()), backtrace)
+        });
+    PanicDetails {
+        message,
+        location,
+        backtrace,
+    }
+}
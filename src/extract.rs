@@ -0,0 +1,231 @@
+use crate::error::Error;
+use crate::form::FormData;
+use crate::Request;
+use futures_util::future::BoxFuture;
+use futures_util::FutureExt;
+use http::HeaderMap;
+use serde::de::DeserializeOwned;
+use std::ops::Deref;
+use std::sync::Arc;
+
+pub use crate::multipart::Multipart;
+
+/// Types that can be extracted from a request to be passed to a handler.
+///
+/// Async handlers may take any number of extractors (up to six) as arguments
+/// instead of the `Request`. Each one is extracted before the handler runs;
+/// if any fails, its error is answered through the app's error hook (see
+/// `App::on_error`) and the handler is not called.
+///
+/// # Example
+///
+/// ```
+/// use serde::Deserialize;
+/// use velto::extract::{Form, Path};
+/// use velto::test::TestRequest;
+/// use velto::{route, App};
+///
+/// #[derive(Deserialize)]
+/// struct Signup {
+///     username: String,
+///     age: u8,
+/// }
+///
+/// async fn signup(Path(team): Path<String>, Form(form): Form<Signup>) -> String {
+///     format!("{} ({}) joined {}", form.username, form.age, team)
+/// }
+///
+/// let mut app = App::new();
+/// route!(app, POST "/teams/:team/signup" => signup);
+///
+/// let res = TestRequest::new("POST", "/teams/rust/signup")
+///     .with_header("Content-Type", "application/x-www-form-urlencoded")
+///     .with_body("username=ferris&age=8")
+///     .send(&app);
+/// assert_eq!(res.body(), "ferris (8) joined rust");
+/// ```
+pub trait FromRequest: Sized + Send + 'static {
+    /// Extracts the value, or returns the error to respond with.
+    fn from_request(req: &Request) -> Result<Self, Error>;
+
+    /// Extracts the value for a handler about to run.
+    ///
+    /// Defaults to [`from_request`](Self::from_request). Extractors that
+    /// block, such as [`Multipart`] writing uploads to disk, override it to
+    /// do that work off the async worker threads.
+    fn extract(req: &Request) -> BoxFuture<'static, Result<Self, Error>> {
+        Box::pin(std::future::ready(Self::from_request(req)))
+    }
+}
+
+impl FromRequest for Request {
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        Ok(req.clone())
+    }
+}
+
+impl<T: FromRequest> FromRequest for Option<T> {
+    /// `None` if the extractor fails, instead of rejecting the request.
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        Ok(T::from_request(req).ok())
+    }
+
+    fn extract(req: &Request) -> BoxFuture<'static, Result<Self, Error>> {
+        Box::pin(T::extract(req).map(|result| Ok(result.ok())))
+    }
+}
+
+impl<T: FromRequest> FromRequest for Result<T, Error> {
+    /// The extractor's result, so the handler can deal with the failure itself.
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        Ok(T::from_request(req))
+    }
+
+    fn extract(req: &Request) -> BoxFuture<'static, Result<Self, Error>> {
+        Box::pin(T::extract(req).map(Ok))
+    }
+}
+
+/// Path parameters deserialized into `T`.
+///
+/// `T` can be a struct with one field per parameter, a tuple of the
+/// parameters in order, or a single value for routes with one parameter.
+/// Fails with `400 Bad Request` if a parameter cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path<T>(pub T);
+
+impl<T: DeserializeOwned + Send + 'static> FromRequest for Path<T> {
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        crate::de::from_pairs(req.params().iter())
+            .map(Path)
+            .map_err(|e| Error::bad_request(format!("Invalid path parameters: {}", e)))
+    }
+}
+
+/// Query string parameters deserialized into `T`.
+///
+/// Fails with `400 Bad Request` if the query string does not match `T`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query<T>(pub T);
+
+impl<T: DeserializeOwned + Send + 'static> FromRequest for Query<T> {
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        crate::de::from_pairs(req.query().iter())
+            .map(Query)
+            .map_err(|e| Error::bad_request(format!("Invalid query string: {}", e)))
+    }
+}
+
+/// A URL-encoded form body deserialized into `T`, as with
+/// [`FormData::deserialize`].
+///
+/// Fails with `415 Unsupported Media Type` unless the request's
+/// `Content-Type` is `application/x-www-form-urlencoded`, `400 Bad Request`
+/// if the body is not valid URL-encoding, and `422 Unprocessable Entity` if
+/// the fields do not match `T`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Form<T>(pub T);
+
+impl<T: DeserializeOwned + Send + 'static> FromRequest for Form<T> {
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        require_content_type(req, "application/x-www-form-urlencoded")?;
+        FormData::try_parse(req.body())?.deserialize().map(Form)
+    }
+}
+
+/// A JSON body deserialized into `T`, or a JSON response. Requires the
+/// `json` feature.
+///
+/// As an extractor it fails with `415 Unsupported Media Type` unless the
+/// request's `Content-Type` is `application/json`, `400 Bad Request` if the
+/// body is empty or not valid JSON (the message says where parsing stopped),
+/// and `422 Unprocessable Entity` if it does not match `T`.
+///
+/// Returned from a handler, `Json(value)` serializes `value` into an
+/// `application/json` response.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: DeserializeOwned + Send + 'static> FromRequest for Json<T> {
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        require_content_type(req, "application/json")?;
+        if req.body().iter().all(u8::is_ascii_whitespace) {
+            return Err(Error::bad_request(
+                "Expected a JSON body, found an empty one",
+            ));
+        }
+        serde_json::from_slice(req.body()).map(Json).map_err(|e| {
+            use serde_json::error::Category;
+            match e.classify() {
+                Category::Data => Error::new(422, format!("Invalid JSON body: {}", e)),
+                Category::Eof => Error::bad_request(format!("Truncated JSON body: {}", e)),
+                Category::Syntax | Category::Io => {
+                    Error::bad_request(format!("Malformed JSON body: {}", e))
+                }
+            }
+        })
+    }
+}
+
+/// The request headers.
+#[derive(Debug, Clone)]
+pub struct Headers(pub HeaderMap);
+
+impl FromRequest for Headers {
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        Ok(Headers(req.headers().clone()))
+    }
+}
+
+/// Application state of type `T`, registered with `App::manage`.
+///
+/// Fails with `500 Internal Server Error` if no state of this type is managed.
+#[derive(Debug)]
+pub struct State<T>(pub Arc<T>);
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        State(self.0.clone())
+    }
+}
+
+impl<T> Deref for State<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Send + Sync + 'static> FromRequest for State<T> {
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        req.state_arc::<T>().map(State).ok_or_else(|| {
+            Error::internal(format!(
+                "No state of type {} is managed by the app",
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+}
+
+/// Checks the request's `Content-Type`, ignoring parameters such as `charset`.
+fn require_content_type(req: &Request, expected: &str) -> Result<(), Error> {
+    let content_type = req
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    match mime.eq_ignore_ascii_case(expected) {
+        true => Ok(()),
+        false => Err(Error::new(
+            415,
+            format!(
+                "Expected Content-Type {}, found {:?}",
+                expected, content_type
+            ),
+        )),
+    }
+}
@@ -1,14 +1,209 @@
+use crate::error::Error;
+use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 use urlencoding::decode;
 
 /// Parses a URL-encoded form body into a HashMap with percent-decoding.
+///
+/// Only the last value of a repeated key is kept; use [`FormData`] to keep
+/// them all.
 pub fn parse(body: &str) -> HashMap<String, String> {
+    parse_pairs(body).into_iter().collect()
+}
+
+/// Parses a raw URL-encoded request body, failing with a `400 Bad Request`
+/// error if it is not valid UTF-8 or contains invalid percent-encoding.
+///
+/// # Example
+///
+/// ```
+/// use velto::form::try_parse;
+///
+/// let form = try_parse(b"name=alice&city=New%20York").unwrap();
+/// assert_eq!(form["city"], "New York");
+///
+/// assert_eq!(try_parse(b"name=%FF").unwrap_err().status(), 400);
+/// assert_eq!(try_parse(&[0xff, 0xfe]).unwrap_err().status(), 400);
+/// ```
+pub fn try_parse(body: &[u8]) -> Result<HashMap<String, String>, Error> {
+    try_parse_pairs(body).map(|pairs| pairs.into_iter().collect())
+}
+
+/// Like [`try_parse`], but keeps every pair in order.
+pub(crate) fn try_parse_pairs(body: &[u8]) -> Result<Vec<(String, String)>, Error> {
+    let body = std::str::from_utf8(body)
+        .map_err(|_| Error::bad_request("form body is not valid UTF-8"))?;
+    body.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, val) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode_part = |part: &str| {
+                decode_component(part).ok_or_else(|| {
+                    Error::bad_request(format!("invalid percent-encoding in form field {:?}", pair))
+                })
+            };
+            Ok((decode_part(key)?, decode_part(val)?))
+        })
+        .collect()
+}
+
+/// Parses a URL-encoded string into percent-decoded key/value pairs,
+/// preserving their order and any repeated keys.
+///
+/// This is the decoding shared by form bodies and request query strings.
+/// `+` decodes to a space, a key without `=` gets an empty value, and pairs
+/// with invalid percent-encoding are skipped.
+///
+/// # Example
+///
+/// ```
+/// use velto::form::parse_pairs;
+///
+/// let pairs = parse_pairs("tag=a&tag=b&q=hello+world&flag");
+/// assert_eq!(pairs[1], ("tag".to_string(), "b".to_string()));
+/// assert_eq!(pairs[2].1, "hello world");
+/// assert_eq!(pairs[3], ("flag".to_string(), String::new()));
+/// ```
+pub fn parse_pairs(body: &str) -> Vec<(String, String)> {
     body.split('&')
+        .filter(|pair| !pair.is_empty())
         .filter_map(|pair| {
-            let mut parts = pair.splitn(2, '=');
-            let key = decode(parts.next()?.trim()).ok()?.to_string();
-            let val = decode(parts.next()?.trim()).ok()?.to_string();
-            Some((key, val))
+            let (key, val) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((decode_component(key)?, decode_component(val)?))
         })
         .collect()
 }
+
+/// Decodes one key or value: `+` is a space, then percent-decoding.
+fn decode_component(part: &str) -> Option<String> {
+    let part = part.trim().replace('+', " ");
+    decode(&part).ok().map(|decoded| decoded.into_owned())
+}
+
+/// A parsed URL-encoded form, keeping every field in order.
+///
+/// Unlike [`parse`], repeated keys such as a checkbox group
+/// (`tags=a&tags=b`) keep all their values, and fields can be deserialized
+/// into a struct, with bracket notation (`user[address][city]`) filling
+/// nested structs.
+///
+/// # Example
+///
+/// ```
+/// use serde::Deserialize;
+/// use velto::form::FormData;
+///
+/// #[derive(Deserialize)]
+/// struct Signup {
+///     user: User,
+///     tags: Vec<String>,
+///     newsletter: bool,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     name: String,
+///     address: Address,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Address {
+///     city: String,
+/// }
+///
+/// let form = FormData::parse(
+///     "user[name]=Ada+Lovelace&user[address][city]=London&tags=a&tags=b&newsletter=on",
+/// );
+/// assert_eq!(form.get("user[name]"), Some("Ada Lovelace"));
+/// assert_eq!(form.get_all("tags"), ["a", "b"]);
+///
+/// let signup: Signup = form.deserialize().unwrap();
+/// assert_eq!(signup.user.address.city, "London");
+/// assert_eq!(signup.tags, ["a", "b"]);
+/// assert!(signup.newsletter);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormData {
+    pairs: Vec<(String, String)>,
+}
+
+impl FormData {
+    /// Parses a URL-encoded string, skipping pairs that cannot be decoded.
+    pub fn parse(body: &str) -> Self {
+        FormData {
+            pairs: parse_pairs(body),
+        }
+    }
+
+    /// Parses a raw request body, failing with a `400 Bad Request` error if
+    /// it is not valid UTF-8 or contains invalid percent-encoding.
+    pub fn try_parse(body: &[u8]) -> Result<Self, Error> {
+        try_parse_pairs(body).map(|pairs| FormData { pairs })
+    }
+
+    /// Returns the first value for the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns every value for the given key, in order of appearance.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.pairs
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// Returns true if the key appears in the form.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.pairs.iter().any(|(k, _)| k == key)
+    }
+
+    /// Iterates over all key/value pairs in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the number of pairs.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Returns true if the form has no fields.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Deserializes the fields into `T`, failing with a
+    /// `422 Unprocessable Entity` error if they do not match.
+    ///
+    /// Repeated keys fill `Vec` fields (otherwise the last value wins),
+    /// bracket notation fills nested structs and, with numeric indices
+    /// (`items[0][name]`), vectors of structs. Checkbox values `on`/`off`
+    /// deserialize as booleans, and empty values as `None`.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        crate::de::from_pairs(self.iter())
+            .map_err(|e| Error::new(422, format!("Invalid form data: {}", e)))
+    }
+}
+
+impl FromIterator<(String, String)> for FormData {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        FormData {
+            pairs: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for FormData {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pairs.into_iter()
+    }
+}
@@ -0,0 +1,132 @@
+use crate::error::Error;
+use crate::extract::FromRequest;
+use crate::response::IntoResponse;
+use crate::router::Handler;
+use crate::{Request, Response};
+use std::future::{ready, Future};
+use std::sync::Arc;
+
+/// Marker for handlers of the form `fn(&Request) -> impl IntoResponse`.
+pub struct SyncMarker;
+
+/// Marker for handlers of the form `async fn(Request) -> impl IntoResponse`,
+/// or more generally async functions taking [extractors](crate::extract).
+pub struct AsyncMarker;
+
+/// Conversion of a function into a route [`Handler`].
+///
+/// Implemented for both synchronous handlers taking `&Request` and
+/// asynchronous handlers taking an owned `Request`, returning anything that
+/// implements [`IntoResponse`] (such as `Result<Response, velto::Error>`), so either can be passed
+/// to `App::route` and the `route!` macro. The marker type `M` only exists to
+/// keep the two implementations apart and is always inferred.
+///
+/// # Example
+///
+/// ```
+/// use velto::{route, App, Request, Response};
+///
+/// fn hello(_req: &Request) -> Response {
+///     Response::from_string("Hello!")
+/// }
+///
+/// async fn user(req: Request) -> Response {
+///     Response::from_string(format!("User {}", req.param("id").unwrap_or("?")))
+/// }
+///
+/// let mut app = App::new();
+/// route!(app, "/hello" => hello);
+/// route!(app, "/users/:id" => user);
+/// ```
+pub trait IntoHandler<M>: Send + Sync + 'static {
+    /// Boxes the function into a type-erased handler.
+    fn into_handler(self) -> Handler;
+}
+
+impl<F, R> IntoHandler<SyncMarker> for F
+where
+    F: Fn(&Request) -> R + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    fn into_handler(self) -> Handler {
+        Arc::new(move |req| match self(&req).into_result() {
+            Ok(response) => Box::pin(ready(response)),
+            Err(error) => Box::pin(handle_error(error, req)),
+        })
+    }
+}
+
+/// Implements `IntoHandler` for async functions taking extractors as arguments.
+macro_rules! impl_async_handler {
+    ($($arg:ident),+) => {
+        impl<F, Fut, $($arg,)+> IntoHandler<(AsyncMarker, $($arg,)+)> for F
+        where
+            F: Fn($($arg,)+) -> Fut + Send + Sync + 'static,
+            Fut: Future + Send + 'static,
+            Fut::Output: IntoResponse,
+            $($arg: FromRequest,)+
+        {
+            #[allow(non_snake_case)]
+            fn into_handler(self) -> Handler {
+                let handler = Arc::new(self);
+                Arc::new(move |req: Request| {
+                    let handler = handler.clone();
+                    Box::pin(async move {
+                        $(
+                            let $arg = match $arg::extract(&req).await {
+                                Ok(value) => value,
+                                Err(error) => return handle_error(error, req).await,
+                            };
+                        )+
+                        match handler($($arg,)+).await.into_result() {
+                            Ok(response) => response,
+                            Err(error) => handle_error(error, req).await,
+                        }
+                    })
+                })
+            }
+        }
+    };
+}
+
+impl_async_handler!(T1);
+impl_async_handler!(T1, T2);
+impl_async_handler!(T1, T2, T3);
+impl_async_handler!(T1, T2, T3, T4);
+impl_async_handler!(T1, T2, T3, T4, T5);
+impl_async_handler!(T1, T2, T3, T4, T5, T6);
+
+/// Passes an error returned by a handler to the app's error hook.
+async fn handle_error(error: Error, req: Request) -> Response {
+    let errors = req.error_handlers().clone();
+    errors.handle(error, req).await
+}
+
+/// Gives untyped closure parameters in `route!` a concrete `Request` type.
+///
+/// Without an annotation, a closure such as `|req| ...` could be either a
+/// sync or an async handler and its parameter type cannot be inferred.
+/// Closures whose body is an `async` block take an owned `Request`,
+/// all others take `&Request`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __velto_handler {
+    (|$req:ident| async $($body:tt)+) => {
+        |$req: $crate::Request| async $($body)+
+    };
+    (move |$req:ident| async $($body:tt)+) => {
+        move |$req: $crate::Request| async $($body)+
+    };
+    (|$req:ident| $($body:tt)+) => {
+        |$req: &$crate::Request| $($body)+
+    };
+    (move |$req:ident| $($body:tt)+) => {
+        move |$req: &$crate::Request| $($body)+
+    };
+    (|_| $($body:tt)+) => {
+        |_: &$crate::Request| $($body)+
+    };
+    ($($handler:tt)+) => {
+        $($handler)+
+    };
+}
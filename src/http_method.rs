@@ -1,8 +1,22 @@
-/// Represents the set of supported HTTP methods.
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// The names `route!` falls back to; see `__velto_method_names!`.
+#[doc(hidden)]
+pub mod __names {
+    pub use super::Method;
+}
+
+/// Represents an HTTP request method.
+///
+/// This enum provides a strongly typed representation of the standard HTTP
+/// request methods, plus an `Extension` variant for anything else
+/// (e.g. WebDAV's `PROPFIND` or a custom `PURGE`).
 ///
-/// This enum provides a simplified and strongly typed representation
-/// of common HTTP request methods.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Methods compare by name, so `Method::Extension("GET".into())` equals
+/// `Method::GET`.
+#[derive(Debug, Clone)]
 pub enum Method {
     /// The HTTP GET method.
     GET,
@@ -18,6 +32,12 @@ pub enum Method {
     OPTIONS,
     /// The HTTP HEAD method.
     HEAD,
+    /// The HTTP TRACE method.
+    TRACE,
+    /// The HTTP CONNECT method.
+    CONNECT,
+    /// Any other method, carrying its name exactly as received (methods are case-sensitive).
+    Extension(String),
 }
 
 impl Method {
@@ -29,8 +49,8 @@ impl Method {
     ///
     /// # Returns
     ///
-    /// A `Method` variant matching the input. If the method is not recognized,
-    /// it defaults to `Method::GET`.
+    /// A `Method` variant matching the input. Methods without a dedicated
+    /// variant are returned as `Method::Extension`.
     ///
     /// # Example
     ///
@@ -40,6 +60,9 @@ impl Method {
     ///
     /// let method = Method::from_hyper(&HyperMethod::POST);
     /// assert_eq!(method, Method::POST);
+    ///
+    /// let purge = HyperMethod::from_bytes(b"PURGE").unwrap();
+    /// assert_eq!(Method::from_hyper(&purge), Method::Extension("PURGE".to_string()));
     /// ```
     pub fn from_hyper(method: &http::Method) -> Self {
         match *method {
@@ -50,7 +73,115 @@ impl Method {
             http::Method::PATCH => Method::PATCH,
             http::Method::OPTIONS => Method::OPTIONS,
             http::Method::HEAD => Method::HEAD,
-            _ => Method::GET, // fallback
+            http::Method::TRACE => Method::TRACE,
+            http::Method::CONNECT => Method::CONNECT,
+            _ => Method::Extension(method.as_str().to_string()),
         }
     }
+
+    /// Returns the method name as it appears on the wire, e.g. `"GET"`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::GET => "GET",
+            Method::POST => "POST",
+            Method::PUT => "PUT",
+            Method::DELETE => "DELETE",
+            Method::PATCH => "PATCH",
+            Method::OPTIONS => "OPTIONS",
+            Method::HEAD => "HEAD",
+            Method::TRACE => "TRACE",
+            Method::CONNECT => "CONNECT",
+            Method::Extension(name) => name,
+        }
+    }
+}
+
+impl PartialEq for Method {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Method {}
+
+impl Hash for Method {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Method {
+    type Err = http::method::InvalidMethod;
+
+    /// Parses a method name. Names are case-sensitive, so `"get"` becomes an extension method.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::http_method::Method;
+    ///
+    /// assert_eq!("DELETE".parse::<Method>().unwrap(), Method::DELETE);
+    /// assert_eq!("PROPFIND".parse::<Method>().unwrap(), Method::Extension("PROPFIND".to_string()));
+    /// assert!("BAD METHOD".parse::<Method>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        http::Method::from_bytes(s.as_bytes()).map(Method::from)
+    }
+}
+
+impl From<http::Method> for Method {
+    fn from(method: http::Method) -> Self {
+        Method::from_hyper(&method)
+    }
+}
+
+impl From<&http::Method> for Method {
+    fn from(method: &http::Method) -> Self {
+        Method::from_hyper(method)
+    }
+}
+
+impl TryFrom<&Method> for http::Method {
+    type Error = http::method::InvalidMethod;
+
+    /// Converts to an `http::Method`, failing if an extension method's name
+    /// is not a valid HTTP token.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::http_method::Method;
+    ///
+    /// let purge = http::Method::try_from(&Method::Extension("PURGE".into())).unwrap();
+    /// assert_eq!(purge.as_str(), "PURGE");
+    /// assert!(http::Method::try_from(&Method::Extension("bad method".into())).is_err());
+    /// ```
+    fn try_from(method: &Method) -> Result<Self, Self::Error> {
+        match method {
+            Method::GET => Ok(http::Method::GET),
+            Method::POST => Ok(http::Method::POST),
+            Method::PUT => Ok(http::Method::PUT),
+            Method::DELETE => Ok(http::Method::DELETE),
+            Method::PATCH => Ok(http::Method::PATCH),
+            Method::OPTIONS => Ok(http::Method::OPTIONS),
+            Method::HEAD => Ok(http::Method::HEAD),
+            Method::TRACE => Ok(http::Method::TRACE),
+            Method::CONNECT => Ok(http::Method::CONNECT),
+            Method::Extension(name) => http::Method::from_bytes(name.as_bytes()),
+        }
+    }
+}
+
+impl TryFrom<Method> for http::Method {
+    type Error = http::method::InvalidMethod;
+
+    fn try_from(method: Method) -> Result<Self, Self::Error> {
+        http::Method::try_from(&method)
+    }
 }
@@ -1,16 +1,29 @@
 pub mod app;
+mod de;
+pub mod error;
+pub mod extract;
 pub mod form;
+pub mod handler;
 pub mod http_method;
 pub mod macros;
 pub mod middleware;
+pub mod multipart;
 pub mod prelude;
 mod reload;
+pub mod request;
 pub mod response;
 pub mod router;
+pub mod scope;
+pub mod state;
 pub mod template;
 pub mod test;
+pub mod url;
+pub mod validation;
 pub use app::App;
 pub use async_tiny::{HeaderName, HeaderValue};
+pub use error::Error;
+#[cfg(feature = "json")]
+pub use extract::Json;
 pub use router::{Handler, Header, Request, Response};
 pub use std::collections::HashMap;
 pub use template::render_template;
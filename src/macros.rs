@@ -1,26 +1,79 @@
 /// render! macro allowing for easy templating
+/// Values can be anything that converts into a `template::Value`: strings,
+/// numbers, booleans, `Vec`s and maps, or `Value::from_serialize(&data)?`
+/// for a `Serialize` type.
+/// An optional third argument adds every pair of a map to the context, such
+/// as the `context()` of `validation::ValidationErrors` or a
+/// `template::Context`.
 /// Example:
 /// ```
 /// use velto::{render, Response};
 ///
 /// let _ = render!("index.html", {
 ///     "title" => "Welcome",
-///     "message" => "Hello, Velto!"
+///     "message" => "Hello, Velto!",
+///     "unread" => 3,
+///     "labels" => vec!["work", "home"],
 /// });
+///
+/// let extra = std::collections::HashMap::from([("message", "Hi again")]);
+/// let _ = render!("index.html", { "title" => "Welcome" }, extra);
 /// ```
 #[macro_export]
 macro_rules! render {
-    ($file:expr, { $($key:expr => $val:expr),* $(,)? }) => {{
-        let mut ctx = std::collections::HashMap::new();
+    ($file:expr, { $($key:expr => $val:expr),* $(,)? } $(, $extra:expr)?) => {{
+        #[allow(unused_mut)]
+        let mut ctx = $crate::template::Context::new();
         $(ctx.insert($key, $val);)*
-        let html = $crate::render_template($file, &ctx);
+        $(
+            for (key, val) in &$extra {
+                ctx.insert(::std::convert::AsRef::<str>::as_ref(key), ::std::clone::Clone::clone(val));
+            }
+        )?
+        let html = $crate::template::render_or_message(&$file, &ctx);
         $crate::Response::from_data(html.into_bytes())
             .with_header("Content-Type: text/html".parse::<$crate::Header>().unwrap())
     }};
 }
 
+/// Like `render!`, but returns `Result<Response, velto::Error>` so that a
+/// missing template can be propagated with `?`. Takes the same values and
+/// optional third argument.
+/// Example:
+/// ```
+/// use velto::{try_render, Error, Response};
+///
+/// fn page() -> Result<Response, Error> {
+///     let res = try_render!("index.html", { "title" => "Welcome" })?;
+///     Ok(res)
+/// }
+///
+/// assert!(page().is_ok());
+/// assert!(try_render!("missing.html", {}).is_err());
+/// ```
+#[macro_export]
+macro_rules! try_render {
+    ($file:expr, { $($key:expr => $val:expr),* $(,)? } $(, $extra:expr)?) => {{
+        #[allow(unused_mut)]
+        let mut ctx = $crate::template::Context::new();
+        $(ctx.insert($key, $val);)*
+        $(
+            for (key, val) in &$extra {
+                ctx.insert(::std::convert::AsRef::<str>::as_ref(key), ::std::clone::Clone::clone(val));
+            }
+        )?
+        $crate::template::render_with_context(&$file, &ctx).map(|html| {
+            $crate::Response::from_data(html.into_bytes())
+                .with_header("Content-Type: text/html".parse::<$crate::Header>().unwrap())
+        })
+    }};
+}
+
 /// Route macro for defining routes
 /// Supports single method, multiple methods, or default GET.
+/// Handlers may be sync (`fn(&Request) -> R`) or async (`async fn(Request) -> R`),
+/// where `R` implements `IntoResponse` (e.g. `Response`, `&str`, `(u16, String)`).
+/// A literal path may be followed by `as "name"` to name the route for `url_for`.
 /// Example:
 /// ```
 /// use velto::{App, Response, route};
@@ -36,26 +89,84 @@ macro_rules! render {
 ///         _ => Response::from_string("Sign up form"),
 ///     }
 /// });
+///
+/// route!(app, "/slow" => |req| async move {
+///     Response::from_string(format!("Took my time with {}", req.url()))
+/// });
+///
+/// route!(app, "/users/:id/edit" as "user_edit" => |_req| {
+///     Response::from_string("Edit user")
+/// });
+/// assert_eq!(app.url_for("user_edit", &[("id", "42")]).unwrap(), "/users/42/edit");
 /// ```
 #[macro_export]
 macro_rules! route {
+    // Named routes: `route!(app, [GET] "/users/:id" as "user" => handler)`
+    ($app:expr, [$($method:ident),+] $path:literal as $name:expr => $($handler:tt)+) => {
+        $crate::route!($app, [$($method),+] $path => $($handler)+);
+        $app.name($name, $path);
+    };
+    ($app:expr, $method:ident $path:literal as $name:expr => $($handler:tt)+) => {
+        $crate::route!($app, $method $path => $($handler)+);
+        $app.name($name, $path);
+    };
+    ($app:expr, $path:literal as $name:expr => $($handler:tt)+) => {
+        $crate::route!($app, $path => $($handler)+);
+        $app.name($name, $path);
+    };
+
     // Multiple methods → same handler
-    ($app:expr, [$($method:ident),+] $path:expr => $handler:expr) => {{
-        $( $app.route($crate::http_method::Method::$method, $path, $handler); )+
-    }};
+    ($app:expr, [$($method:ident),+] $path:expr => $($handler:tt)+) => {
+        $crate::__velto_method_names!();
+        $app.route_all(
+            &[$($crate::http_method::Method::$method),+],
+            $path,
+            $crate::__velto_handler!($($handler)+),
+        );
+    };
 
     // Single method
-    ($app:expr, $method:ident $path:expr => $handler:expr) => {{
-        $app.route($crate::http_method::Method::$method, $path, $handler);
-    }};
+    ($app:expr, $method:ident $path:expr => $($handler:tt)+) => {
+        $crate::__velto_method_names!();
+        $app.route(
+            $crate::http_method::Method::$method,
+            $path,
+            $crate::__velto_handler!($($handler)+),
+        );
+    };
 
     // Default to GET
-    ($app:expr, $path:expr => $handler:expr) => {{
-        $app.route($crate::http_method::Method::GET, $path, $handler);
-    }};
+    ($app:expr, $path:expr => $($handler:tt)+) => {
+        $crate::__velto_method_names!();
+        $app.route(
+            $crate::http_method::Method::GET,
+            $path,
+            $crate::__velto_handler!($($handler)+),
+        );
+    };
+}
+
+/// Refers to `Method` from the scope `route!` is called in.
+///
+/// `route!` takes the bare variant names of `Method` (`GET`, `POST`), so
+/// callers often import `Method` next to it. The macro itself uses a full
+/// path, which would leave that import unused; this marks it as used. The
+/// glob import only provides `Method` when the caller has no import of
+/// their own, since an explicit import in the same block takes precedence
+/// over a glob. Expanded as statements, not a block, for that reason.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __velto_method_names {
+    () => {
+        #[allow(unused_imports)]
+        use $crate::http_method::__names::*;
+        #[allow(unused_imports)]
+        use Method as _;
+    };
 }
 
-/// route_any! macro for registering a handler across all standard HTTP methods
+/// route_any! macro for registering a handler across all standard HTTP methods,
+/// including HEAD and OPTIONS
 /// Example:
 /// ```
 /// use velto::{App, Response, route_any};
@@ -67,10 +178,12 @@ macro_rules! route {
 /// ```
 #[macro_export]
 macro_rules! route_any {
-    ($app:expr, $path:expr => $handler:expr) => {{
+    ($app:expr, $path:expr => $($handler:tt)+) => {{
         use $crate::http_method::Method::*;
-        for method in [GET, POST, PUT, DELETE, PATCH, OPTIONS] {
-            $app.route(method, $path, $handler);
-        }
+        $app.route_all(
+            &[GET, HEAD, POST, PUT, DELETE, PATCH, OPTIONS],
+            $path,
+            $crate::__velto_handler!($($handler)+),
+        );
     }};
 }
@@ -1,12 +1,146 @@
+use crate::router::Handler;
 use crate::{Request, Response};
+use futures_util::future::BoxFuture;
+use std::cell::Cell;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
 
-/// A middleware function takes a request and the next handler, and returns a response.
-pub type Middleware = fn(&Request, &dyn Fn(&Request) -> Response) -> Response;
+thread_local! {
+    /// Set on a blocking thread while it runs synchronous middleware.
+    static IN_SYNC: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A middleware takes a request and the rest of the chain, and returns a response.
+///
+/// Middleware is usually written as an `async fn(Request, Next) -> Response`
+/// and registered with `App::use_middleware`.
+pub type Middleware = Arc<dyn Fn(Request, Next) -> BoxFuture<'static, Response> + Send + Sync>;
+
+/// The signature of a synchronous middleware function.
+///
+/// Synchronous middleware is still supported and runs on tokio's blocking
+/// thread pool so that calling `next` can wait for async handlers. Sync
+/// middleware further down the chain runs on the same thread, so a request
+/// holds one blocking thread however many are stacked.
+pub type SyncMiddleware = fn(&Request, &dyn Fn(&Request) -> Response) -> Response;
+
+/// The remainder of the middleware chain, ending in the route handler.
+#[derive(Clone)]
+pub struct Next {
+    middlewares: Arc<Vec<Middleware>>,
+    index: usize,
+    handler: Handler,
+}
+
+impl Next {
+    pub(crate) fn new(middlewares: Arc<Vec<Middleware>>, handler: Handler) -> Self {
+        Next {
+            middlewares,
+            index: 0,
+            handler,
+        }
+    }
+
+    /// Passes the request to the next middleware, or to the handler if none remain.
+    pub async fn run(mut self, req: Request) -> Response {
+        match self.middlewares.get(self.index).cloned() {
+            Some(mw) => {
+                self.index += 1;
+                mw(req, self).await
+            }
+            None => (self.handler)(req).await,
+        }
+    }
+}
+
+/// Wraps a handler so it runs behind the given middleware stack.
+///
+/// Used to scope middleware to a group of routes rather than the whole app.
+pub(crate) fn wrap(middlewares: Arc<Vec<Middleware>>, handler: Handler) -> Handler {
+    Arc::new(move |req| Box::pin(Next::new(middlewares.clone(), handler.clone()).run(req)))
+}
+
+/// Marker for middleware of the form `fn(&Request, &dyn Fn(&Request) -> Response) -> Response`.
+pub struct SyncMarker;
+
+/// Marker for middleware of the form `async fn(Request, Next) -> Response`.
+pub struct AsyncMarker;
+
+/// Conversion of a function into a [`Middleware`].
+///
+/// Implemented for async middleware taking `(Request, Next)` and for the
+/// original synchronous signature, so both can be passed to `App::use_middleware`.
+pub trait IntoMiddleware<M> {
+    /// Boxes the function into a type-erased middleware.
+    fn into_middleware(self) -> Middleware;
+}
+
+impl<F, Fut> IntoMiddleware<AsyncMarker> for F
+where
+    F: Fn(Request, Next) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send + 'static,
+{
+    fn into_middleware(self) -> Middleware {
+        Arc::new(move |req, next| Box::pin(self(req, next)))
+    }
+}
+
+impl<F> IntoMiddleware<SyncMarker> for F
+where
+    F: Fn(&Request, &dyn Fn(&Request) -> Response) -> Response + Send + Sync + 'static,
+{
+    fn into_middleware(self) -> Middleware {
+        let mw = Arc::new(self);
+        Arc::new(move |req, next| {
+            let mw = mw.clone();
+            Box::pin(async move {
+                let handle = tokio::runtime::Handle::current();
+                let names = crate::url::current().unwrap_or_default();
+                let env = crate::template::environment::current();
+                let call = move || {
+                    let _dispatching = crate::error::DispatchGuard::enter();
+                    let outer = IN_SYNC.with(|in_sync| in_sync.replace(true));
+                    // Catch here so the panic's location travels with the payload.
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        mw(&req, &|req: &Request| {
+                            let next = next.clone().run(req.clone());
+                            let next = crate::template::environment::scope(env.clone(), next);
+                            handle.block_on(crate::url::with_names(names.clone(), next))
+                        })
+                    }))
+                    .map_err(|payload| (payload, crate::error::take_panic_record()));
+                    IN_SYNC.with(|in_sync| in_sync.set(outer));
+                    result
+                };
+                let result = if IN_SYNC.with(Cell::get) {
+                    // Already on the blocking thread of an outer sync
+                    // middleware, which is waiting on this chain. Taking
+                    // another thread per layer would let stacked middleware
+                    // exhaust the pool with every request half done. Off the
+                    // worker threads, `block_in_place` runs `call` right here.
+                    tokio::task::block_in_place(call)
+                } else {
+                    tokio::task::spawn_blocking(call)
+                        .await
+                        .unwrap_or_else(|e| panic::resume_unwind(e.into_panic()))
+                };
+                match result {
+                    Ok(res) => res,
+                    Err((payload, record)) => {
+                        crate::error::restore_panic_record(record);
+                        panic::resume_unwind(payload)
+                    }
+                }
+            })
+        })
+    }
+}
 
 /// Logs the request method and URL before and after handling.
-pub fn logger(req: &Request, next: &dyn Fn(&Request) -> Response) -> Response {
+pub async fn logger(req: Request, next: Next) -> Response {
     println!("📥 {} {}", req.method(), req.url());
-    let res = next(req);
+    let res = next.run(req).await;
     println!("📤 Responded with {}", res.status_code());
     res
 }
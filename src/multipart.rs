@@ -0,0 +1,612 @@
+//! Parsing of `multipart/form-data` request bodies, as sent by forms with
+//! `enctype="multipart/form-data"` and file inputs.
+//!
+//! # Example
+//!
+//! ```
+//! use velto::multipart::Multipart;
+//! use velto::test::{MultipartBody, TestRequest};
+//! use velto::{route, App};
+//!
+//! let mut app = App::new();
+//! route!(app, POST "/avatar" => |form: Multipart| async move {
+//!     let user = form.field("user").unwrap_or("anonymous");
+//!     match form.file("avatar") {
+//!         Some(file) => format!("{} uploaded {} ({} bytes)", user, file.filename(), file.len()),
+//!         None => format!("{} uploaded nothing", user),
+//!     }
+//! });
+//!
+//! let body = MultipartBody::new()
+//!     .text("user", "ferris")
+//!     .file("avatar", "crab.png", "image/png", b"\x89PNG...");
+//! let res = TestRequest::new("POST", "/avatar").multipart(body).send(&app);
+//! assert_eq!(res.body(), "ferris uploaded crab.png (7 bytes)");
+//! ```
+
+use crate::error::Error;
+use crate::extract::FromRequest;
+use crate::Request;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use memchr::memmem;
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Size limits applied when parsing a multipart body.
+///
+/// Register them for an app with `App::set_multipart_limits`; requests whose
+/// body or files exceed them are rejected with `413 Payload Too Large`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    max_file_size: usize,
+    max_total_size: usize,
+    memory_threshold: usize,
+}
+
+impl Limits {
+    /// The default limits: 10 MiB per file, 20 MiB per request, and files
+    /// over 256 KiB written to a temp file.
+    pub const fn new() -> Self {
+        Self {
+            max_file_size: 10 * 1024 * 1024,
+            max_total_size: 20 * 1024 * 1024,
+            memory_threshold: 256 * 1024,
+        }
+    }
+
+    /// Sets the largest accepted file, in bytes.
+    pub const fn max_file_size(mut self, bytes: usize) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Sets the largest accepted body, in bytes, including all parts.
+    pub const fn max_total_size(mut self, bytes: usize) -> Self {
+        self.max_total_size = bytes;
+        self
+    }
+
+    /// Sets the size above which uploaded files are written to a temp file
+    /// instead of being kept in memory.
+    pub const fn memory_threshold(mut self, bytes: usize) -> Self {
+        self.memory_threshold = bytes;
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A parsed `multipart/form-data` body: text fields and uploaded files, in
+/// the order they were sent.
+///
+/// As an extractor it is parsed on the blocking thread pool, since large
+/// files are written to disk, and uses the limits set with `App::set_multipart_limits`
+/// (or [`Limits::default`]), and fails with `415 Unsupported Media Type` for
+/// other content types, `400 Bad Request` for a malformed body and
+/// `413 Payload Too Large` when a limit is exceeded.
+#[derive(Debug, Default)]
+pub struct Multipart {
+    fields: Vec<(String, String)>,
+    files: Vec<FilePart>,
+}
+
+impl Multipart {
+    /// Parses a body sent with the given `Content-Type` header.
+    pub fn parse(content_type: &str, body: &[u8], limits: &Limits) -> Result<Self, Error> {
+        Self::read(content_type, body, limits)
+    }
+
+    /// Parses a body read from `reader`, enforcing the limits while reading:
+    /// it stops with `413 Payload Too Large` as soon as the body or a file
+    /// goes over its limit, and files over the memory threshold are written
+    /// to their temp file as they are read.
+    pub fn read(content_type: &str, reader: impl io::Read, limits: &Limits) -> Result<Self, Error> {
+        let boundary = boundary(content_type)?;
+        let delimiter = format!("--{}", boundary).into_bytes();
+        // The delimiter ends a part's content together with its leading CRLF.
+        let end = [&b"\r\n"[..], &delimiter].concat();
+        let mut input = Input {
+            reader,
+            buf: Vec::new(),
+            read: 0,
+            limit: limits.max_total_size,
+            eof: false,
+        };
+
+        // Anything before the first delimiter is a preamble and is ignored.
+        input.skip_past(&delimiter)?;
+        let mut multipart = Multipart::default();
+        loop {
+            while input.buf.len() < 2 && input.fill()? {}
+            if input.buf.starts_with(b"--") {
+                return Ok(multipart);
+            }
+            if !input.buf.starts_with(b"\r\n") {
+                return Err(malformed());
+            }
+            input.consume(2);
+
+            let headers = input.headers()?;
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.as_str())
+            };
+            let disposition = header("content-disposition")
+                .ok_or_else(|| Error::bad_request("multipart part has no Content-Disposition"))?;
+            let params = disposition_params(disposition);
+            let param = |key: &str| {
+                params
+                    .iter()
+                    .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                    .map(|(_, v)| v.clone())
+            };
+            let name = param("name")
+                .ok_or_else(|| Error::bad_request("multipart part has no field name"))?;
+
+            match param("filename") {
+                Some(filename) => {
+                    let content_type = header("content-type")
+                        .unwrap_or("application/octet-stream")
+                        .to_string();
+                    let mut writer = FileWriter::new(&filename, limits);
+                    input.content(&end, |chunk| writer.write(chunk))?;
+                    let data = writer.finish();
+                    multipart.files.push(FilePart {
+                        name,
+                        filename,
+                        content_type,
+                        data,
+                    });
+                }
+                None => {
+                    let mut value = Vec::new();
+                    input.content(&end, |chunk| {
+                        value.extend_from_slice(chunk);
+                        Ok(())
+                    })?;
+                    let value = String::from_utf8(value).map_err(|_| {
+                        Error::bad_request(format!("multipart field {:?} is not valid UTF-8", name))
+                    })?;
+                    multipart.fields.push((name, value));
+                }
+            }
+            input.consume(end.len());
+        }
+    }
+
+    /// Returns the value of the text field `name`, the last one if repeated.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns all text fields as name/value pairs.
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    /// Returns the first file uploaded as `name`.
+    pub fn file(&self, name: &str) -> Option<&FilePart> {
+        self.files.iter().find(|file| file.name == name)
+    }
+
+    /// Returns all uploaded files.
+    pub fn files(&self) -> &[FilePart] {
+        &self.files
+    }
+
+    /// Takes ownership of the uploaded files.
+    pub fn into_files(self) -> Vec<FilePart> {
+        self.files
+    }
+}
+
+impl FromRequest for Multipart {
+    fn from_request(req: &Request) -> Result<Self, Error> {
+        let content_type = req
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        let limits = req.state::<Limits>().copied().unwrap_or_default();
+        Multipart::parse(content_type, req.body(), &limits)
+    }
+
+    fn extract(req: &Request) -> BoxFuture<'static, Result<Self, Error>> {
+        let req = req.clone();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || Self::from_request(&req))
+                .await
+                .unwrap_or_else(|e| panic::resume_unwind(e.into_panic()))
+        })
+    }
+}
+
+/// An uploaded file.
+#[derive(Debug)]
+pub struct FilePart {
+    name: String,
+    filename: String,
+    content_type: String,
+    data: FileData,
+}
+
+/// Where an uploaded file's contents are kept.
+#[derive(Debug)]
+enum FileData {
+    Memory(Bytes),
+    Disk(TempFile),
+}
+
+impl FilePart {
+    /// The form field the file was uploaded as.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The file name sent by the client. It is not sanitized, so do not use
+    /// it as a path on its own.
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// The file's content type, `application/octet-stream` if none was sent.
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// The file's size in bytes.
+    pub fn len(&self) -> usize {
+        match &self.data {
+            FileData::Memory(bytes) => bytes.len(),
+            FileData::Disk(file) => file.len,
+        }
+    }
+
+    /// Returns true if the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The file's contents, if it was small enough to be kept in memory.
+    pub fn bytes(&self) -> Option<&Bytes> {
+        match &self.data {
+            FileData::Memory(bytes) => Some(bytes),
+            FileData::Disk(_) => None,
+        }
+    }
+
+    /// The temp file holding the contents, if the file was too large to be
+    /// kept in memory. It is deleted when the `FilePart` is dropped.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.data {
+            FileData::Memory(_) => None,
+            FileData::Disk(file) => Some(&file.path),
+        }
+    }
+
+    /// Reads the file's contents, wherever they are kept.
+    pub fn read(&self) -> io::Result<Bytes> {
+        match &self.data {
+            FileData::Memory(bytes) => Ok(bytes.clone()),
+            FileData::Disk(file) => fs::read(&file.path).map(Bytes::from),
+        }
+    }
+
+    /// Saves the file to `dest`, moving the temp file when possible.
+    pub fn save(self, dest: impl AsRef<Path>) -> io::Result<()> {
+        let dest = dest.as_ref();
+        match self.data {
+            FileData::Memory(bytes) => fs::write(dest, bytes),
+            FileData::Disk(file) => {
+                if fs::rename(&file.path, dest).is_err() {
+                    fs::copy(&file.path, dest)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A temp file that is deleted when dropped.
+#[derive(Debug)]
+struct TempFile {
+    path: PathBuf,
+    len: usize,
+}
+
+impl TempFile {
+    /// Creates a new, empty temp file with an unpredictable name, readable
+    /// only by the current user where the platform supports it.
+    fn create() -> io::Result<(Self, fs::File)> {
+        loop {
+            let path = std::env::temp_dir().join(format!("velto-upload-{:016x}", random()));
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(file) => return Ok((Self { path, len: 0 }, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A random number for temp file names, from the standard library's
+/// randomly keyed hasher.
+fn random() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+/// Collects an uploaded file's contents, in memory until they go over the
+/// memory threshold and then in a temp file.
+struct FileWriter<'a> {
+    filename: &'a str,
+    limits: &'a Limits,
+    memory: Vec<u8>,
+    disk: Option<(TempFile, fs::File)>,
+}
+
+impl<'a> FileWriter<'a> {
+    fn new(filename: &'a str, limits: &'a Limits) -> Self {
+        Self {
+            filename,
+            limits,
+            memory: Vec::new(),
+            disk: None,
+        }
+    }
+
+    fn write(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        let len = self.len() + chunk.len();
+        if len > self.limits.max_file_size {
+            return Err(too_large(format!(
+                "file {:?} is too large, the limit is {} bytes",
+                self.filename, self.limits.max_file_size
+            )));
+        }
+        if self.disk.is_none() && len > self.limits.memory_threshold {
+            let (temp, mut file) = TempFile::create()?;
+            io::Write::write_all(&mut file, &std::mem::take(&mut self.memory))?;
+            self.disk = Some((temp, file));
+        }
+        match &mut self.disk {
+            Some((temp, file)) => {
+                io::Write::write_all(file, chunk)?;
+                temp.len = len;
+            }
+            None => self.memory.extend_from_slice(chunk),
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        match &self.disk {
+            Some((temp, _)) => temp.len,
+            None => self.memory.len(),
+        }
+    }
+
+    fn finish(self) -> FileData {
+        match self.disk {
+            Some((temp, _)) => FileData::Disk(temp),
+            None => FileData::Memory(Bytes::from(self.memory)),
+        }
+    }
+}
+
+/// How much of the body is read at a time.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// The largest accepted header section of a single part.
+const MAX_HEADERS_SIZE: usize = 16 * 1024;
+
+/// A multipart body being read, with the bytes read but not yet parsed.
+struct Input<R> {
+    reader: R,
+    buf: Vec<u8>,
+    read: usize,
+    limit: usize,
+    eof: bool,
+}
+
+impl<R: io::Read> Input<R> {
+    /// Reads the next chunk of the body into the buffer, failing once the
+    /// body goes over the size limit. Returns false at the end of the body.
+    fn fill(&mut self) -> Result<bool, Error> {
+        if self.eof {
+            return Ok(false);
+        }
+        let mut chunk = [0; CHUNK_SIZE];
+        let n = loop {
+            match self.reader.read(&mut chunk) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    return Err(Error::bad_request(format!(
+                        "cannot read multipart body: {}",
+                        e
+                    )))
+                }
+            }
+        };
+        if n == 0 {
+            self.eof = true;
+            return Ok(false);
+        }
+        self.read += n;
+        if self.read > self.limit {
+            return Err(too_large(format!(
+                "multipart body is too large, the limit is {} bytes",
+                self.limit
+            )));
+        }
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(true)
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.buf.drain(..n);
+    }
+
+    /// Discards everything up to and including the first `pattern`.
+    fn skip_past(&mut self, pattern: &[u8]) -> Result<(), Error> {
+        loop {
+            if let Some(start) = memmem::find(&self.buf, pattern) {
+                self.consume(start + pattern.len());
+                return Ok(());
+            }
+            self.consume(self.buf.len().saturating_sub(pattern.len() - 1));
+            if !self.fill()? {
+                return Err(malformed());
+            }
+        }
+    }
+
+    /// Reads a part's headers as name/value pairs, up to and including the
+    /// blank line that ends them.
+    fn headers(&mut self) -> Result<Vec<(String, String)>, Error> {
+        let malformed_headers = || Error::bad_request("malformed multipart part headers");
+        loop {
+            if self.buf.starts_with(b"\r\n") {
+                self.consume(2);
+                return Ok(Vec::new());
+            }
+            if let Some(end) = memmem::find(&self.buf, b"\r\n\r\n") {
+                let head = std::str::from_utf8(&self.buf[..end]).map_err(|_| {
+                    Error::bad_request("multipart part headers are not valid UTF-8")
+                })?;
+                let headers = head
+                    .split("\r\n")
+                    .filter(|line| !line.is_empty())
+                    .map(|line| {
+                        line.split_once(':')
+                            .map(|(name, value)| {
+                                (name.trim().to_string(), value.trim().to_string())
+                            })
+                            .ok_or_else(malformed_headers)
+                    })
+                    .collect::<Result<_, _>>()?;
+                self.consume(end + 4);
+                return Ok(headers);
+            }
+            if self.buf.len() > MAX_HEADERS_SIZE || !self.fill()? {
+                return Err(malformed_headers());
+            }
+        }
+    }
+
+    /// Passes a part's content to `sink` in chunks, stopping before `end`.
+    fn content(
+        &mut self,
+        end: &[u8],
+        mut sink: impl FnMut(&[u8]) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        loop {
+            if let Some(at) = memmem::find(&self.buf, end) {
+                sink(&self.buf[..at])?;
+                self.consume(at);
+                return Ok(());
+            }
+            // Keep enough bytes to find `end` if it straddles two chunks.
+            let safe = self.buf.len().saturating_sub(end.len() - 1);
+            sink(&self.buf[..safe])?;
+            self.consume(safe);
+            if !self.fill()? {
+                return Err(malformed());
+            }
+        }
+    }
+}
+
+fn malformed() -> Error {
+    Error::bad_request("malformed multipart body")
+}
+
+fn too_large(message: String) -> Error {
+    Error::new(413, message)
+}
+
+/// Extracts the boundary from a `multipart/form-data` content type.
+fn boundary(content_type: &str) -> Result<String, Error> {
+    let mut params = content_type.split(';');
+    let mime = params.next().unwrap_or("").trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return Err(Error::new(
+            415,
+            format!(
+                "Expected Content-Type multipart/form-data, found {:?}",
+                content_type
+            ),
+        ));
+    }
+    params
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty())
+        .ok_or_else(|| Error::bad_request("multipart Content-Type has no boundary"))
+}
+
+/// Parses the parameters of a `Content-Disposition` value such as
+/// `form-data; name="avatar"; filename="me.png"`.
+fn disposition_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = value.split_once(';').map_or("", |(_, rest)| rest);
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim_start_matches(|c: char| c == ';' || c.is_whitespace());
+        let after = after.trim_start();
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                (value, &quoted[end..])
+            }
+            None => {
+                let end = after.find(';').unwrap_or(after.len());
+                (after[..end].trim().to_string(), &after[end..])
+            }
+        };
+        params.push((key.trim().to_string(), value));
+        rest = remaining;
+    }
+    params
+}
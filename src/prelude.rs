@@ -1,6 +1,10 @@
 pub use crate::form::parse as parse_form;
+pub use crate::middleware::Next;
 pub use crate::render_template;
-pub use crate::response::{redirect, redirect_with_status};
+pub use crate::response::{redirect, redirect_with_status, IntoResponse};
 pub use crate::App;
+pub use crate::Error;
+#[cfg(feature = "json")]
+pub use crate::Json;
 pub use crate::{render, route};
 pub use crate::{Header, Request, Response};
@@ -1,7 +1,9 @@
+use crate::template::Environment;
 use futures_util::{SinkExt, StreamExt};
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 use tokio::sync::broadcast;
 use tokio_tungstenite::accept_async;
@@ -39,13 +41,19 @@ async fn find_free_port(start: u16) -> Option<u16> {
 ///
 /// * `tx` - A broadcast channel used to notify reload events.
 /// * `watch_paths` - A list of directories to watch for changes.
-pub(crate) async fn start(tx: broadcast::Sender<()>, watch_paths: Vec<String>) {
+/// * `environment` - The app's template environment, whose cached templates
+///   are discarded when their files change.
+pub(crate) async fn start(
+    tx: broadcast::Sender<()>,
+    watch_paths: Vec<String>,
+    environment: Arc<Environment>,
+) {
     let port = find_free_port(35729).await.unwrap_or(35729);
     crate::dev::set_reload_port(port);
 
     tokio::spawn(start_ws_server(tx.clone(), port));
     tokio::spawn(async move {
-        watch_files(tx.clone(), watch_paths).await;
+        watch_files(tx.clone(), watch_paths, environment).await;
     });
 }
 
@@ -88,17 +96,26 @@ async fn start_ws_server(tx: broadcast::Sender<()>, port: u16) {
 
 /// Watches the given directories for file changes and triggers reload events.
 ///
+/// Each change also discards the changed files from the template caches of
+/// the app's environment and the default one, so the reloaded page is
+/// rendered from the edited templates while other templates stay cached.
+///
 /// This uses the `notify` crate to monitor changes recursively.
 ///
 /// # Arguments
 ///
 /// * `tx` - Broadcast channel for reload events.
 /// * `paths` - List of directories to watch.
-async fn watch_files(tx: broadcast::Sender<()>, paths: Vec<String>) {
+/// * `environment` - The app's template environment.
+async fn watch_files(tx: broadcast::Sender<()>, paths: Vec<String>, environment: Arc<Environment>) {
     let mut watcher = RecommendedWatcher::new(
         move |res: Result<Event, _>| match res {
             Ok(event) => {
                 println!("🔁 File change detected: {:?}", event);
+                for path in &event.paths {
+                    environment.invalidate(path);
+                    crate::template::environment::default().invalidate(path);
+                }
                 let _ = tx.send(());
             }
             Err(e) => println!("❌ Watcher error: {:?}", e),
@@ -0,0 +1,286 @@
+use crate::error::ErrorHandlers;
+use crate::form::parse_pairs;
+use crate::router::Params;
+use crate::state::StateMap;
+use crate::url::{RouteNames, UrlError};
+use bytes::Bytes;
+use http::{HeaderMap, Method};
+use std::sync::Arc;
+
+/// An incoming HTTP request as seen by route handlers and middleware.
+///
+/// Wraps the method, URL, headers and body received by the server together with
+/// the parsed query string and any path parameters captured while matching the
+/// route pattern.
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: Method,
+    url: String,
+    headers: HeaderMap,
+    body: Bytes,
+    params: Params,
+    query: Query,
+    state: Vec<Arc<StateMap>>,
+    names: Arc<RouteNames>,
+    errors: Arc<ErrorHandlers>,
+}
+
+impl Request {
+    /// Creates a fake Request for testing purposes.
+    ///
+    /// This is useful for exercising handlers and middleware without starting a server.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::Request;
+    ///
+    /// let req = Request::fake(&http::Method::POST, "/submit", b"username=alice");
+    /// assert_eq!(req.url(), "/submit");
+    /// ```
+    pub fn fake(method: &Method, url: &str, body: &[u8]) -> Self {
+        Request {
+            method: method.clone(),
+            url: url.to_string(),
+            headers: HeaderMap::new(),
+            body: Bytes::copy_from_slice(body),
+            params: Params::default(),
+            query: Query::parse(url),
+            state: Vec::new(),
+            names: Arc::default(),
+            errors: Arc::default(),
+        }
+    }
+
+    /// Builds a Request from one received by the underlying `async_tiny` server.
+    pub(crate) fn from_tiny(request: &async_tiny::Request) -> Self {
+        Request {
+            method: request.method().clone(),
+            url: request.url().to_string(),
+            headers: request.headers().clone(),
+            body: request.body().clone(),
+            params: Params::default(),
+            query: Query::parse(request.url()),
+            state: Vec::new(),
+            names: Arc::default(),
+            errors: Arc::default(),
+        }
+    }
+
+    /// Returns the request headers for modification, e.g. by `TestRequest`.
+    pub(crate) fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Attaches the path parameters captured by the router.
+    pub(crate) fn with_params(mut self, params: Params) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Attaches the application state registered with `App::manage`.
+    pub(crate) fn with_state(mut self, state: Arc<StateMap>) -> Self {
+        self.state = vec![state];
+        self
+    }
+
+    /// Attaches the app's named routes so handlers can call `url_for`.
+    pub(crate) fn with_route_names(mut self, names: Arc<RouteNames>) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Attaches the app's error handlers, used for errors returned by handlers.
+    pub(crate) fn with_error_handlers(mut self, errors: Arc<ErrorHandlers>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Returns the error handlers of the app handling this request.
+    pub(crate) fn error_handlers(&self) -> &Arc<ErrorHandlers> {
+        &self.errors
+    }
+
+    /// Adds the state of a mounted app on top of the existing state.
+    pub(crate) fn with_state_layer(mut self, state: Arc<StateMap>) -> Self {
+        self.state.push(state);
+        self
+    }
+
+    /// Returns the request URL, including the query string if present.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the path component of the URL, without the query string.
+    pub fn path(&self) -> &str {
+        self.url.split_once('?').map_or(&self.url, |(path, _)| path)
+    }
+
+    /// Returns the raw query string (without the leading `?`), if present.
+    pub fn query_string(&self) -> Option<&str> {
+        self.url.split_once('?').map(|(_, query)| query)
+    }
+
+    /// Returns the parsed, percent-decoded query parameters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::Request;
+    ///
+    /// let req = Request::fake(&http::Method::GET, "/search?q=rust&tag=a&tag=b", b"");
+    /// assert_eq!(req.path(), "/search");
+    /// assert_eq!(req.query().get("q"), Some("rust"));
+    /// assert_eq!(req.query().get_all("tag"), vec!["a", "b"]);
+    /// ```
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// Returns the HTTP method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the request headers.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the raw request body.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Returns the value of a named path parameter, if the matched route captured it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::test::TestRequest;
+    /// use velto::{route, App, Response};
+    ///
+    /// let mut app = App::new();
+    /// route!(app, "/users/:id" => |req| {
+    ///     Response::from_string(format!("User {}", req.param("id").unwrap_or("?")))
+    /// });
+    ///
+    /// let res = TestRequest::new("GET", "/users/42").send(&app);
+    /// assert_eq!(res.body(), "User 42");
+    /// ```
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name)
+    }
+
+    /// Returns all path parameters captured by the matched route.
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Returns the application state of type `T` registered with `App::manage`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::test::TestRequest;
+    /// use velto::{route, App, Response};
+    ///
+    /// struct Config {
+    ///     site_name: String,
+    /// }
+    ///
+    /// let mut app = App::new();
+    /// app.manage(Config { site_name: "Velto".to_string() });
+    /// route!(app, "/" => |req| {
+    ///     let config = req.state::<Config>().unwrap();
+    ///     Response::from_string(config.site_name.clone())
+    /// });
+    ///
+    /// assert_eq!(TestRequest::new("GET", "/").send(&app).body(), "Velto");
+    /// ```
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.iter().rev().find_map(|layer| layer.get::<T>())
+    }
+
+    /// Returns a shared handle to the application state of type `T`.
+    pub(crate) fn state_arc<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.state
+            .iter()
+            .rev()
+            .find_map(|layer| layer.get_arc::<T>())
+    }
+
+    /// Builds the URL of a named route of the app handling this request.
+    ///
+    /// See [`RouteNames::url_for`] for how parameters are filled in.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::test::TestRequest;
+    /// use velto::{route, App, Response};
+    ///
+    /// let mut app = App::new();
+    /// route!(app, "/users/:id" as "user" => |_req| Response::from_string("user"));
+    /// route!(app, "/" => |req| {
+    ///     Response::from_string(req.url_for("user", &[("id", "42")]).unwrap())
+    /// });
+    ///
+    /// assert_eq!(TestRequest::new("GET", "/").send(&app).body(), "/users/42");
+    /// ```
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        self.names.url_for(name, params)
+    }
+}
+
+/// Query string parameters, percent-decoded and kept in order.
+///
+/// Repeated keys such as `?tag=a&tag=b` are preserved; use [`Query::get_all`]
+/// to retrieve every value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pairs: Vec<(String, String)>,
+}
+
+impl Query {
+    fn parse(url: &str) -> Self {
+        let pairs = url
+            .split_once('?')
+            .map(|(_, query)| parse_pairs(query))
+            .unwrap_or_default();
+        Query { pairs }
+    }
+
+    /// Returns the first value for the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns every value for the given key, in order of appearance.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.pairs
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// Returns true if the key appears in the query string.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.pairs.iter().any(|(k, _)| k == key)
+    }
+
+    /// Iterates over all key/value pairs in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns true if there are no query parameters.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
@@ -1,5 +1,206 @@
+use crate::error::Error;
 use crate::{Header, HeaderName, HeaderValue, Response};
 
+/// Conversion of a handler's return value into a [`Response`].
+///
+/// Handlers may return any type implementing this trait:
+///
+/// - `Response`
+/// - `&str` and `String` (`text/plain`), `Vec<u8>` (`application/octet-stream`)
+/// - `(status, body)` and `(status, headers, body)`, where `headers` is any
+///   iterable of name/value pairs such as `[("Location", "/users/1")]`
+/// - `Option<T>`, where `None` becomes a `404 Not Found` error
+/// - `Json<T>` (`application/json`) with the `json` feature
+/// - `Result<T, E>` where `E: Into<velto::Error>`
+///
+/// Errors are handed to the app's error hook (see `App::on_error`) rather
+/// than converted directly.
+///
+/// # Example
+///
+/// ```
+/// use velto::test::TestRequest;
+/// use velto::{route, App};
+///
+/// let mut app = App::new();
+/// route!(app, "/hello" => |_req| "Hello!");
+/// route!(app, POST "/users" => |_req| (201, [("Location", "/users/1")], "created"));
+/// route!(app, "/users/:id" => |req| {
+///     req.param("id").filter(|id| *id == "1").map(|_| "alice")
+/// });
+///
+/// assert_eq!(TestRequest::new("GET", "/hello").send(&app).body(), "Hello!");
+///
+/// let res = TestRequest::new("POST", "/users").send(&app);
+/// assert_eq!(res.status_code(), 201);
+/// assert_eq!(res.headers().get("location").unwrap(), "/users/1");
+///
+/// assert_eq!(TestRequest::new("GET", "/users/2").send(&app).status_code(), 404);
+/// ```
+pub trait IntoResponse {
+    /// Converts the value into a response.
+    fn into_response(self) -> Response;
+
+    /// Converts the value into a response, keeping errors apart so that the
+    /// app's error hook can turn them into a response.
+    fn into_result(self) -> Result<Response, Error>
+    where
+        Self: Sized,
+    {
+        Ok(self.into_response())
+    }
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for &str {
+    /// A `text/plain` response.
+    fn into_response(self) -> Response {
+        self.to_string().into_response()
+    }
+}
+
+impl IntoResponse for String {
+    /// A `text/plain` response.
+    fn into_response(self) -> Response {
+        Response::from_string(self).with_content_type("text/plain; charset=utf-8")
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    /// An `application/octet-stream` response.
+    fn into_response(self) -> Response {
+        Response::from_data(self).with_content_type("application/octet-stream")
+    }
+}
+
+/// Sets the status of a tuple response, failing for codes outside 100-999.
+fn with_status(response: Response, status: u16) -> Result<Response, Error> {
+    http::StatusCode::from_u16(status)
+        .map(|_| response.with_status_code(status))
+        .map_err(|_| Error::internal(format!("invalid response status {}", status)))
+}
+
+impl<B: IntoResponse> IntoResponse for (u16, B) {
+    /// The body's response with the given status code.
+    ///
+    /// An invalid status code results in a `500` response.
+    fn into_response(self) -> Response {
+        self.into_result().unwrap_or_else(Error::into_response)
+    }
+
+    fn into_result(self) -> Result<Response, Error> {
+        with_status(self.1.into_result()?, self.0)
+    }
+}
+
+impl<H, K, V, B> IntoResponse for (u16, H, B)
+where
+    H: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+    B: IntoResponse,
+{
+    /// The body's response with the given status code and extra headers.
+    ///
+    /// A header replaces one of the same name set by the body. A name given
+    /// more than once keeps every value, joined with `, ` into one field since
+    /// a response holds one field per name; `Set-Cookie` values cannot be
+    /// joined, so repeating it is an error. Invalid header names or values,
+    /// and invalid status codes, result in a `500` response.
+    fn into_response(self) -> Response {
+        self.into_result().unwrap_or_else(Error::into_response)
+    }
+
+    fn into_result(self) -> Result<Response, Error> {
+        let (status, headers, body) = self;
+        let mut fields: Vec<(HeaderName, String)> = Vec::new();
+        for (name, value) in headers {
+            let (name, value) = (name.as_ref(), value.as_ref());
+            let header = Header::new(name, value).map_err(|_| {
+                Error::internal(format!("invalid response header {:?}: {:?}", name, value))
+            })?;
+            match fields.iter_mut().find(|(name, _)| *name == header.0) {
+                Some((name, _)) if *name == http::header::SET_COOKIE => {
+                    return Err(Error::internal(
+                        "a response can carry only one Set-Cookie header",
+                    ))
+                }
+                Some((_, joined)) => {
+                    joined.push_str(", ");
+                    joined.push_str(value.trim());
+                }
+                None => fields.push((header.0, value.trim().to_string())),
+            }
+        }
+        let mut response = with_status(body.into_result()?, status)?;
+        for (name, value) in fields {
+            let value = HeaderValue::from_str(&value).expect("joined from valid header values");
+            response = response.with_header(Header(name, value));
+        }
+        Ok(response)
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for Option<T> {
+    /// The value's response, or `404 Not Found` for `None`.
+    fn into_response(self) -> Response {
+        self.into_result().unwrap_or_else(Error::into_response)
+    }
+
+    fn into_result(self) -> Result<Response, Error> {
+        match self {
+            Some(value) => value.into_result(),
+            None => Err(Error::not_found("Not Found")),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    /// The built-in response for the error's status, without consulting the app.
+    fn into_response(self) -> Response {
+        crate::error::default_response(self.status())
+    }
+
+    fn into_result(self) -> Result<Response, Error> {
+        Err(self)
+    }
+}
+
+impl<T: IntoResponse, E: Into<Error>> IntoResponse for Result<T, E> {
+    fn into_response(self) -> Response {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(error) => error.into().into_response(),
+        }
+    }
+
+    fn into_result(self) -> Result<Response, Error> {
+        match self {
+            Ok(value) => value.into_result(),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize> IntoResponse for crate::extract::Json<T> {
+    /// An `application/json` response, or a `500` if serialization fails.
+    fn into_response(self) -> Response {
+        self.into_result().unwrap_or_else(Error::into_response)
+    }
+
+    fn into_result(self) -> Result<Response, Error> {
+        let body = serde_json::to_vec(&self.0)
+            .map_err(|e| Error::internal(format!("failed to serialize JSON response: {}", e)))?;
+        Ok(Response::from_data(body).with_content_type("application/json"))
+    }
+}
+
 /// Creates a 302 Found redirect response to the specified location.
 ///
 /// This is a convenience wrapper around [`redirect_with_status`] for the common case.
@@ -1,5 +1,357 @@
-pub use async_tiny::{Header, Request, Response};
+pub use crate::request::Request;
+pub use async_tiny::{Header, Response};
+use futures_util::future::BoxFuture;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use urlencoding::decode;
 
-/// A route handler takes a reference to an async_tiny::Request
-/// and returns an async_tiny::Response.
-pub type Handler = Box<dyn Fn(&Request) -> Response + Send + Sync>;
+/// A type-erased route handler: takes a velto Request and resolves to an
+/// async_tiny::Response.
+///
+/// Both sync and async functions are converted into this form through
+/// [`IntoHandler`](crate::handler::IntoHandler).
+pub type Handler = Arc<dyn Fn(Request) -> BoxFuture<'static, Response> + Send + Sync>;
+
+/// Path parameters captured while matching a route pattern.
+///
+/// Route paths may contain named segments (`/users/:id`) and a trailing
+/// wildcard (`/files/*rest`). Captured values are percent-decoded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    entries: Vec<(String, String)>,
+}
+
+impl Params {
+    /// Returns the value captured for the given parameter name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Iterates over captured parameters in the order they appear in the route path.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the number of captured parameters.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no parameters were captured.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn push(&mut self, name: &str, raw: &str) {
+        let value = decode(raw)
+            .map(|v| v.into_owned())
+            .unwrap_or_else(|_| raw.to_string());
+        self.entries.push((name.to_string(), value));
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len);
+    }
+}
+
+/// Errors raised when a route cannot be added to a [`Router`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    /// The same path was registered twice.
+    Duplicate(String),
+    /// The path overlaps an existing route ambiguously,
+    /// e.g. `/users/:id` and `/users/:name`.
+    Conflict {
+        /// The path being registered.
+        path: String,
+        /// The already registered path it conflicts with.
+        existing: String,
+    },
+    /// The path is malformed, e.g. an unnamed parameter or a wildcard that is not last.
+    Invalid(String),
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::Duplicate(path) => write!(f, "route '{}' is already registered", path),
+            RouteError::Conflict { path, existing } => write!(
+                f,
+                "route '{}' conflicts with existing route '{}'",
+                path, existing
+            ),
+            RouteError::Invalid(path) => write!(f, "route '{}' is not a valid path", path),
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+/// The result of a successful [`Router::at`] lookup.
+pub struct Match<'a, T> {
+    /// The value registered for the matched route.
+    pub value: &'a T,
+    /// Parameters captured from the request path.
+    pub params: Params,
+    /// The route path as it was registered, e.g. `/users/:id`.
+    pub route: &'a str,
+}
+
+/// A prefix tree mapping route paths to values.
+///
+/// Each node corresponds to one path segment. Lookups walk the tree segment by
+/// segment with a fixed priority: static segments first, then a named parameter,
+/// then a trailing wildcard, backtracking when a branch dead-ends. Matching is
+/// therefore independent of registration order and scales with the depth of the
+/// path rather than the number of routes.
+///
+/// # Example
+///
+/// ```
+/// use velto::router::Router;
+///
+/// let mut router = Router::new();
+/// router.insert("/users/:id", "user").unwrap();
+/// router.insert("/users/me", "me").unwrap();
+///
+/// let matched = router.at("/users/42").unwrap();
+/// assert_eq!(*matched.value, "user");
+/// assert_eq!(matched.params.get("id"), Some("42"));
+/// assert_eq!(*router.at("/users/me").unwrap().value, "me");
+/// ```
+pub struct Router<T> {
+    root: Node<T>,
+}
+
+struct Node<T> {
+    statics: HashMap<String, Node<T>>,
+    param: Option<(String, Box<Node<T>>)>,
+    wildcard: Option<(String, Box<Node<T>>)>,
+    endpoint: Option<Endpoint<T>>,
+}
+
+struct Endpoint<T> {
+    route: String,
+    value: T,
+}
+
+enum Segment<'a> {
+    Static(&'a str),
+    Param(&'a str),
+    Wildcard(&'a str),
+}
+
+fn split(path: &str) -> Vec<&str> {
+    path.trim_start_matches('/').split('/').collect()
+}
+
+fn parse(path: &str) -> Result<Vec<Segment<'_>>, RouteError> {
+    let parts = split(path);
+    let last = parts.len() - 1;
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| {
+            if let Some(name) = part.strip_prefix(':') {
+                if name.is_empty() {
+                    return Err(RouteError::Invalid(path.to_string()));
+                }
+                Ok(Segment::Param(name))
+            } else if let Some(name) = part.strip_prefix('*') {
+                if name.is_empty() || i != last {
+                    return Err(RouteError::Invalid(path.to_string()));
+                }
+                Ok(Segment::Wildcard(name))
+            } else {
+                Ok(Segment::Static(part))
+            }
+        })
+        .collect()
+}
+
+/// Returns the child node for a named parameter or wildcard, creating it if needed.
+///
+/// Two routes may share a parameter position only if they use the same name,
+/// otherwise it would be ambiguous which name the captured value belongs to.
+fn named_child<'a, T>(
+    slot: &'a mut Option<(String, Box<Node<T>>)>,
+    name: &str,
+    path: &str,
+) -> Result<&'a mut Node<T>, RouteError> {
+    if let Some((existing, child)) = slot {
+        if existing != name {
+            return Err(RouteError::Conflict {
+                path: path.to_string(),
+                existing: child.any_route().unwrap_or(existing).to_string(),
+            });
+        }
+    }
+    Ok(slot
+        .get_or_insert_with(|| (name.to_string(), Box::new(Node::new())))
+        .1
+        .as_mut())
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Node {
+            statics: HashMap::new(),
+            param: None,
+            wildcard: None,
+            endpoint: None,
+        }
+    }
+
+    /// Any route registered at or below this node, used in conflict messages.
+    fn any_route(&self) -> Option<&str> {
+        if let Some(endpoint) = &self.endpoint {
+            return Some(&endpoint.route);
+        }
+        [&self.param, &self.wildcard]
+            .into_iter()
+            .flatten()
+            .find_map(|(_, node)| node.any_route())
+            .or_else(|| self.statics.values().find_map(|node| node.any_route()))
+    }
+
+    fn lookup<'a>(&'a self, parts: &[&str], params: &mut Params) -> Option<&'a Endpoint<T>> {
+        let Some((part, rest)) = parts.split_first() else {
+            return self.endpoint.as_ref();
+        };
+
+        if let Some(found) = self
+            .statics
+            .get(*part)
+            .and_then(|node| node.lookup(rest, params))
+        {
+            return Some(found);
+        }
+
+        if let Some((name, node)) = &self.param {
+            if !part.is_empty() {
+                let len = params.len();
+                params.push(name, part);
+                if let Some(found) = node.lookup(rest, params) {
+                    return Some(found);
+                }
+                params.truncate(len);
+            }
+        }
+
+        if let Some((name, node)) = &self.wildcard {
+            let remainder = parts.join("/");
+            if !remainder.is_empty() {
+                params.push(name, &remainder);
+                return node.endpoint.as_ref();
+            }
+        }
+
+        None
+    }
+
+    fn collect<'a>(&'a self, out: &mut Vec<(&'a str, &'a T)>) {
+        if let Some(endpoint) = &self.endpoint {
+            out.push((&endpoint.route, &endpoint.value));
+        }
+        for node in self.statics.values() {
+            node.collect(out);
+        }
+        for (_, node) in [&self.param, &self.wildcard].into_iter().flatten() {
+            node.collect(out);
+        }
+    }
+}
+
+impl<T> Router<T> {
+    /// Creates an empty router.
+    pub fn new() -> Self {
+        Router { root: Node::new() }
+    }
+
+    /// Registers a value for the given route path.
+    ///
+    /// Returns an error if the path is already registered, is malformed, or
+    /// conflicts with an existing route.
+    pub fn insert(&mut self, path: &str, value: T) -> Result<(), RouteError> {
+        let slot = self.slot(path)?;
+        if slot.is_some() {
+            return Err(RouteError::Duplicate(path.to_string()));
+        }
+        *slot = Some(Endpoint {
+            route: path.to_string(),
+            value,
+        });
+        Ok(())
+    }
+
+    /// Returns the value registered for the given route path, inserting a default
+    /// value if the path has not been registered yet.
+    ///
+    /// This is how `App` accumulates several methods under a single path.
+    pub fn entry(&mut self, path: &str) -> Result<&mut T, RouteError>
+    where
+        T: Default,
+    {
+        let slot = self.slot(path)?;
+        let endpoint = slot.get_or_insert_with(|| Endpoint {
+            route: path.to_string(),
+            value: T::default(),
+        });
+        Ok(&mut endpoint.value)
+    }
+
+    /// Looks up the route matching a request path.
+    pub fn at(&self, path: &str) -> Option<Match<'_, T>> {
+        let mut params = Params::default();
+        let endpoint = self.root.lookup(&split(path), &mut params)?;
+        Some(Match {
+            value: &endpoint.value,
+            params,
+            route: &endpoint.route,
+        })
+    }
+
+    /// Returns all registered routes and their values, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &T)> {
+        let mut routes = Vec::new();
+        self.root.collect(&mut routes);
+        routes.sort_by(|a, b| a.0.cmp(b.0));
+        routes.into_iter()
+    }
+
+    /// Returns the number of registered routes.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns true if no routes are registered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Walks to the endpoint slot for `path`, creating nodes along the way.
+    fn slot(&mut self, path: &str) -> Result<&mut Option<Endpoint<T>>, RouteError> {
+        let mut node = &mut self.root;
+        for segment in parse(path)? {
+            node = match segment {
+                Segment::Static(part) => node
+                    .statics
+                    .entry(part.to_string())
+                    .or_insert_with(Node::new),
+                Segment::Param(name) => named_child(&mut node.param, name, path)?,
+                Segment::Wildcard(name) => named_child(&mut node.wildcard, name, path)?,
+            };
+        }
+        Ok(&mut node.endpoint)
+    }
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
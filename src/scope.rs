@@ -0,0 +1,125 @@
+use crate::handler::IntoHandler;
+use crate::http_method::Method;
+use crate::middleware::{self, IntoMiddleware, Middleware};
+use crate::router::Handler;
+use std::sync::Arc;
+
+/// A group of routes sharing a path prefix and a middleware stack.
+///
+/// Created with `App::scope`. Routes registered on a scope are mounted under
+/// its prefix, and middleware added with [`Scope::use_middleware`] only runs
+/// for those routes, inside any app-wide middleware. Scopes nest: an inner
+/// scope extends the prefix and runs its middleware inside the outer scope's.
+///
+/// Works with the `route!` macro just like `App`.
+///
+/// # Example
+///
+/// ```
+/// use velto::middleware::Next;
+/// use velto::test::TestRequest;
+/// use velto::{route, App, Request, Response};
+///
+/// async fn require_admin(req: Request, next: Next) -> Response {
+///     match req.query().get("token") {
+///         Some("secret") => next.run(req).await,
+///         _ => Response::from_string("Unauthorized").with_status_code(401),
+///     }
+/// }
+///
+/// let mut app = App::new();
+/// app.scope("/admin", |admin| {
+///     admin.use_middleware(require_admin);
+///     route!(admin, "/users" => |_req| Response::from_string("all users"));
+/// });
+/// route!(app, "/" => |_req| Response::from_string("public"));
+///
+/// assert_eq!(TestRequest::new("GET", "/admin/users").send(&app).status_code(), 401);
+/// assert_eq!(TestRequest::new("GET", "/admin/users?token=secret").send(&app).body(), "all users");
+/// assert_eq!(TestRequest::new("GET", "/").send(&app).body(), "public");
+/// ```
+pub struct Scope {
+    prefix: String,
+    middlewares: Vec<Middleware>,
+    routes: Vec<(Method, String, Handler)>,
+    names: Vec<(String, String)>,
+}
+
+impl Scope {
+    pub(crate) fn new(prefix: &str) -> Self {
+        Scope {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            middlewares: Vec::new(),
+            routes: Vec::new(),
+            names: Vec::new(),
+        }
+    }
+
+    /// Registers a middleware that applies only to routes in this scope and its nested scopes.
+    pub fn use_middleware<M>(&mut self, mw: impl IntoMiddleware<M>) {
+        self.middlewares.push(mw.into_middleware());
+    }
+
+    /// Registers a route handler under this scope's prefix.
+    pub fn route<M>(&mut self, method: Method, path: &str, handler: impl IntoHandler<M>) {
+        self.routes.push((
+            method,
+            join_path(&self.prefix, path),
+            handler.into_handler(),
+        ));
+    }
+
+    /// Registers the same handler for multiple methods under this scope's prefix.
+    pub fn route_all<M>(&mut self, methods: &[Method], path: &str, handler: impl IntoHandler<M>) {
+        let handler = handler.into_handler();
+        let path = join_path(&self.prefix, path);
+        for method in methods {
+            self.routes
+                .push((method.clone(), path.clone(), handler.clone()));
+        }
+    }
+
+    /// Names the route at `path` (relative to this scope) for use with `url_for`.
+    pub fn name(&mut self, name: &str, path: &str) {
+        self.names
+            .push((name.to_string(), join_path(&self.prefix, path)));
+    }
+
+    /// Creates a nested scope whose prefix is appended to this one.
+    pub fn scope(&mut self, prefix: &str, build: impl FnOnce(&mut Scope)) {
+        let mut scope = Scope::new(&join_path(&self.prefix, prefix));
+        build(&mut scope);
+        self.names.append(&mut scope.names);
+        self.routes.extend(scope.into_routes());
+    }
+
+    /// Removes and returns the route names registered on this scope.
+    pub(crate) fn take_names(&mut self) -> Vec<(String, String)> {
+        std::mem::take(&mut self.names)
+    }
+
+    /// Consumes the scope, wrapping each handler in the scope's middleware.
+    pub(crate) fn into_routes(self) -> Vec<(Method, String, Handler)> {
+        if self.middlewares.is_empty() {
+            return self.routes;
+        }
+        let middlewares = Arc::new(self.middlewares);
+        self.routes
+            .into_iter()
+            .map(|(method, path, handler)| {
+                (method, path, middleware::wrap(middlewares.clone(), handler))
+            })
+            .collect()
+    }
+}
+
+/// Joins a scope prefix and a route path, e.g. `/api` + `/users` → `/api/users`.
+/// A route path of `/` maps to the prefix itself.
+pub(crate) fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    match path.trim_start_matches('/') {
+        "" if prefix.is_empty() => "/".to_string(),
+        "" => prefix.to_string(),
+        rest => format!("{}/{}", prefix, rest),
+    }
+}
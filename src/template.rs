@@ -1,16 +1,48 @@
-use regex::Regex;
+use crate::error::Error;
+use crate::url::{RouteNames, UrlError};
 use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
+
+mod cache;
+pub(crate) mod environment;
+pub(crate) mod filters;
+mod parser;
+mod render;
+mod ser;
+mod value;
+
+pub use environment::Environment;
+pub use value::{Context, Value};
 
 /// Renders an HTML template with context interpolation, includes, and inheritance.
 ///
-/// This function loads a template file from the `templates/` directory and processes it
-/// using a lightweight templating engine. It supports:
+/// This function loads a template file from the `templates/` directory (or the root of the
+/// app's [`Environment`]) and processes it using a lightweight templating engine. It supports:
 ///
-/// - `{{ key }}`: Variable interpolation from the provided context.
+/// - `{{ key }}`: Variable interpolation from the provided context. In `.html`
+///   templates values are HTML-escaped, including inside attribute values.
+///   This follows the file a value is written in, not the page it ends up
+///   in, so included `.txt` templates are not escaped.
+/// - `{{ key | safe }}` or `{{{ key }}}`: Interpolation without escaping, for
+///   trusted markup.
+/// - `{{ key | upper | truncate(20) }}`: Filters, applied left to right. The
+///   built-in filters are `upper`, `lower`, `trim`, `default`, `length`,
+///   `join`, `truncate`, `format_number`, `date`, `urlencode` and `json`;
+///   apps can add their own with `App::add_filter`, which templates rendered
+///   while the app handles a request can use (see [`Environment`]).
+/// - `{{ user.address.city }}`, `{{ items[0] }}`: Fields of maps and items of
+///   lists, for values passed through a [`Context`] or `render!`.
+/// - `{% if cond %}...{% elif cond %}...{% else %}...{% endif %}` and
+///   `{% for item in items %}...{% empty %}...{% endfor %}`: Conditionals and loops.
+/// - `{% raw %}...{% endraw %}`: Text that is output as is, for `{{`, `{%`
+///   and `{#` that are not template syntax.
 /// - `{% include 'file.html' %}`: Includes and renders another template inline.
 /// - `{% extends 'base.html' %}`: Inherits from a base template.
 /// - `{% block name %}...{% endblock %}`: Defines content blocks for overriding in child templates.
+/// - `{{ url_for('user_edit', id=user_id) }}`: The URL of a named route. Arguments are
+///   `'quoted'` literals or context keys. An unknown route or missing
+///   parameter fails rendering with the template name and line.
 ///
 /// # Arguments
 ///
@@ -64,50 +96,82 @@ use std::fs;
 ///
 /// - If development mode is enabled (`App::enable_dev_mode()`), a LiveReload script is injected
 ///   before the closing `</body>` tag to enable automatic browser refresh on file changes.
-/// - Missing templates or includes will render a fallback error message.
+/// - Templates are parsed once and cached; in dev mode the file watcher
+///   discards changed templates from the cache (see [`clear_cache`]).
+/// - Missing templates or includes will render a fallback error message; use
+///   [`try_render_template`] to get an error instead.
 pub fn render_template(file: &str, context: &HashMap<&str, &str>) -> String {
-    let mut contents = load_template(file);
-
-    // Handle {% extends 'base.html' %}
-    let extends_re = Regex::new(r"\{%\s*extends\s*'([^']+)'\s*%\}").unwrap();
-    if let Some(caps) = extends_re.captures(&contents) {
-        let base_file = &caps[1];
-        let base_contents = load_template(base_file);
+    render_or_message(file, &Context::from(context))
+}
 
-        // Extract blocks from child
-        let block_re =
-            Regex::new(r"\{%\s*block\s+(\w+)\s*%\}(?s)(.*?)\{%\s*endblock\s*%\}").unwrap();
-        let mut blocks = HashMap::new();
-        for caps in block_re.captures_iter(&contents) {
-            blocks.insert(caps[1].to_string(), caps[2].to_string());
-        }
+/// Renders a template with a [`Context`], or the error message on failure.
+/// A missing include or base template renders the message in its place.
+/// Used by `render!`.
+#[doc(hidden)]
+pub fn render_or_message(file: &str, context: &Context) -> String {
+    render_page(
+        &environment::current(),
+        file,
+        context,
+        render::Missing::Inline,
+    )
+    .unwrap_or_else(|e| format!("<h1>{}</h1>", e))
+}
 
-        // Replace blocks in base
-        let base_block_re =
-            Regex::new(r"\{%\s*block\s+(\w+)\s*%\}(?s)(.*?)\{%\s*endblock\s*%\}").unwrap();
-        contents = base_block_re
-            .replace_all(&base_contents, |caps: &regex::Captures| {
-                blocks
-                    .get(&caps[1])
-                    .cloned()
-                    .unwrap_or_else(|| caps[2].to_string())
-            })
-            .to_string();
-    }
+/// Renders a template like [`render_template`], but fails instead of
+/// rendering a fallback message when the template or one of its includes or
+/// base templates cannot be loaded.
+///
+/// The error has status `500`, so a handler can propagate it with `?`;
+/// the [`try_render!`](crate::try_render) macro does this for you.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashMap;
+/// use velto::template::try_render_template;
+///
+/// let err = try_render_template("missing.html", &HashMap::new()).unwrap_err();
+/// assert_eq!(err.status(), 500);
+/// assert_eq!(err.to_string(), "Template 'missing.html' not found");
+/// ```
+pub fn try_render_template(file: &str, context: &HashMap<&str, &str>) -> Result<String, Error> {
+    render_with_context(file, &Context::from(context))
+}
 
-    // Handle {% include 'header.html' %}
-    let include_re = Regex::new(r"\{%\s*include\s*'([^']+)'\s*%\}").unwrap();
-    contents = include_re
-        .replace_all(&contents, |caps: &regex::Captures| load_template(&caps[1]))
-        .to_string();
+/// Renders a template with a [`Context`], whose values can be lists and
+/// maps as well as strings. Fails like [`try_render_template`], and also
+/// on template syntax errors.
+///
+/// # Example
+///
+/// ```
+/// use velto::template::{render_with_context, Context};
+///
+/// let mut context = Context::new();
+/// context.insert("title", "Inbox");
+/// context.insert("message", "3 new messages");
+///
+/// let html = render_with_context("index.html", &context).unwrap();
+/// assert!(html.contains("<p>3 new messages</p>"));
+/// ```
+pub fn render_with_context(file: &str, context: &Context) -> Result<String, Error> {
+    render_page(
+        &environment::current(),
+        file,
+        context,
+        render::Missing::Fail,
+    )
+}
 
-    // Handle {{ key }} interpolation
-    let var_re = Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap();
-    contents = var_re
-        .replace_all(&contents, |caps: &regex::Captures| {
-            context.get(&caps[1]).copied().unwrap_or("")
-        })
-        .to_string();
+/// Renders a template and injects the LiveReload script in dev mode.
+fn render_page(
+    env: &Environment,
+    file: &str,
+    context: &Context,
+    missing: render::Missing,
+) -> Result<String, Error> {
+    let mut contents = render::render(env, file, context, missing)?;
 
     // Inject LiveReload if in dev mode
     if crate::is_dev_mode() {
@@ -129,10 +193,136 @@ ws.onerror = (e) => console.error("❌ WebSocket error", e);
         }
     }
 
-    contents
+    Ok(contents)
+}
+
+/// Where in an HTML document the text seen so far ends, as far as escaping
+/// is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HtmlState {
+    /// Element content.
+    Text,
+    /// Just after `<`, deciding whether a tag starts.
+    TagOpen,
+    /// Inside a tag, outside attribute values.
+    Tag,
+    /// After `=` in a tag, before the value.
+    AttrStart,
+    /// Inside a `"`-quoted attribute value.
+    DoubleQuoted,
+    /// Inside a `'`-quoted attribute value.
+    SingleQuoted,
+    /// Inside an unquoted attribute value.
+    Unquoted,
 }
 
-fn load_template(file: &str) -> String {
-    let path = format!("templates/{}", file);
-    fs::read_to_string(path).unwrap_or_else(|_| format!("<h1>Template '{}' not found</h1>", file))
+impl HtmlState {
+    fn next(self, c: char) -> Self {
+        use HtmlState::*;
+        match (self, c) {
+            (Text, '<') => TagOpen,
+            (Text, _) => Text,
+            (TagOpen, c) if c.is_ascii_alphabetic() || c == '/' => Tag,
+            (TagOpen, '<') => TagOpen,
+            (TagOpen, _) => Text,
+            (Tag, '>') => Text,
+            (Tag, '=') => AttrStart,
+            (Tag, _) => Tag,
+            (AttrStart, '"') => DoubleQuoted,
+            (AttrStart, '\'') => SingleQuoted,
+            (AttrStart, '>') => Text,
+            (AttrStart, c) if c.is_whitespace() => AttrStart,
+            (AttrStart, _) => Unquoted,
+            (DoubleQuoted, '"') | (SingleQuoted, '\'') => Tag,
+            (DoubleQuoted, _) => DoubleQuoted,
+            (SingleQuoted, _) => SingleQuoted,
+            (Unquoted, '>') => Text,
+            (Unquoted, c) if c.is_whitespace() => Tag,
+            (Unquoted, _) => Unquoted,
+        }
+    }
+}
+
+/// Escapes `&`, `<`, `>`, `"` and `'`, making text safe to insert into
+/// element content and quoted attribute values.
+///
+/// # Example
+///
+/// ```
+/// use velto::template::escape_html;
+///
+/// assert_eq!(
+///     escape_html(r#"<a href="x">Tom & 'Jerry'</a>"#),
+///     "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#x27;Jerry&#x27;&lt;/a&gt;"
+/// );
+/// ```
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes every ASCII character other than letters and digits, so the value
+/// cannot end an unquoted attribute value or start another attribute.
+fn escape_unquoted_attribute(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c.is_ascii() && !c.is_ascii_alphanumeric() {
+            true => escaped.push_str(&format!("&#x{:02X};", c as u32)),
+            false => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Checks every `url_for` call in the templates under `dir` against `names`.
+///
+/// A missing directory is not an error.
+pub(crate) fn check_url_for(dir: &Path, names: &RouteNames) -> Result<(), UrlError> {
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        if path.is_dir() {
+            if let Ok(entries) = fs::read_dir(&path) {
+                pending.extend(entries.flatten().map(|entry| entry.path()));
+            }
+            continue;
+        }
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+        // Syntax errors are reported when the template is rendered.
+        let Ok(template) = parser::parse(&path.display().to_string(), &contents) else {
+            continue;
+        };
+        for (name, params) in template.url_for_calls() {
+            let args: Vec<(&str, &str)> = params.into_iter().map(|key| (key, "x")).collect();
+            names
+                .url_for(name, &args)
+                .map_err(|error| UrlError::InTemplate {
+                    template: path.display().to_string(),
+                    error: Box::new(error),
+                })?;
+        }
+    }
+    Ok(())
+}
+
+/// Discards the parsed templates cached by the current [`Environment`]: the
+/// app's while it handles a request, and the default one elsewhere. Each
+/// template is read from disk again the next time it is rendered.
+///
+/// Templates are parsed once and then cached. In dev mode the file watcher
+/// discards changed templates; call this if templates change at runtime
+/// otherwise, or [`Environment::clear_cache`] on a specific environment.
+pub fn clear_cache() {
+    environment::current().clear_cache();
 }
@@ -0,0 +1,90 @@
+//! The cache of parsed templates.
+//!
+//! Each [`Environment`](super::Environment) has its own cache. Templates are
+//! read and parsed on first use and kept until the cache is cleared or the
+//! file they were read from is invalidated; in dev mode the file watcher
+//! invalidates changed files, so edits show up on the next render.
+
+use super::parser::{self, Template};
+use crate::error::Error;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+#[derive(Default)]
+pub(crate) struct Cache {
+    entries: RwLock<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    /// Parsed templates by name, with the file each was read from.
+    templates: HashMap<String, (PathBuf, Arc<Template>)>,
+    /// Bumped whenever templates are discarded, so a template read before
+    /// that is not cached after it.
+    generation: u64,
+}
+
+impl Cache {
+    /// Returns the parsed template `file` under `root`, reading and parsing
+    /// it on first use, or `None` if it does not exist. Missing templates
+    /// and syntax errors are not cached.
+    pub fn get(&self, root: &Path, file: &str) -> Result<Option<Arc<Template>>, Error> {
+        let generation = {
+            let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+            if let Some((_, template)) = entries.templates.get(file) {
+                return Ok(Some(template.clone()));
+            }
+            entries.generation
+        };
+        let path = root.join(file);
+        let Ok(source) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        let template = Arc::new(parser::parse(file, &source)?);
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        if entries.generation == generation {
+            let path = normalize(&path);
+            entries
+                .templates
+                .insert(file.to_string(), (path, template.clone()));
+        }
+        Ok(Some(template))
+    }
+
+    /// Forgets every parsed template.
+    pub fn clear(&self) {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.templates.clear();
+        entries.generation += 1;
+    }
+
+    /// Forgets the templates read from `path`, or from files under it if it
+    /// is a directory.
+    pub fn invalidate(&self, path: &Path) {
+        let changed = normalize(path);
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries
+            .templates
+            .retain(|_, (path, _)| !path.starts_with(&changed));
+        entries.generation += 1;
+    }
+}
+
+/// Makes `path` absolute and resolves `..` and symlinks, so paths reported
+/// by the file watcher match the paths templates were read from. A path that
+/// no longer exists, such as a deleted file, is resolved through its parent.
+fn normalize(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+    let parent = path.parent().and_then(|parent| match parent {
+        p if p.as_os_str().is_empty() => fs::canonicalize(".").ok(),
+        p => fs::canonicalize(p).ok(),
+    });
+    match (parent, path.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
@@ -0,0 +1,154 @@
+//! The environment templates are rendered in.
+
+use super::cache::Cache;
+use super::filters::{FilterFn, Filters};
+use super::parser::Template;
+use super::value::{Context, Value};
+use crate::error::Error;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+
+tokio::task_local! {
+    static CURRENT: Arc<Environment>;
+}
+
+/// What templates are rendered with: the directory they are loaded from,
+/// the filters available to them and the cache of parsed templates.
+///
+/// Every [`App`](crate::App) has an environment, holding the filters added
+/// with [`App::add_filter`](crate::App::add_filter), which `render_template`,
+/// `render!` and friends use while the app handles a request. Anywhere
+/// else, such as in a task spawned from a handler, they use a default
+/// environment with only the built-in filters; render through
+/// [`App::environment`](crate::App::environment) there instead.
+///
+/// Since each environment caches its own templates, apps in the same
+/// process can load templates from different directories, and clearing one
+/// cache leaves the others alone.
+///
+/// # Example
+///
+/// ```
+/// use velto::template::{Context, Environment, Value};
+///
+/// let env = Environment::new();
+/// env.add_filter("shout", |value: &Value, _args: &[Value]| {
+///     Ok(format!("{}!", value).to_uppercase().into())
+/// });
+///
+/// let mut context = Context::new();
+/// context.insert("title", "Inbox");
+/// context.insert("message", "hi");
+/// assert!(env.render("index.html", &context).is_ok());
+/// ```
+pub struct Environment {
+    root: PathBuf,
+    filters: RwLock<Arc<Filters>>,
+    cache: Cache,
+}
+
+impl Environment {
+    /// Creates an environment loading templates from `templates/`, with only
+    /// the built-in filters.
+    pub fn new() -> Self {
+        Self::with_root("templates")
+    }
+
+    /// Creates an environment loading templates from `root`.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Environment {
+            root: root.into(),
+            filters: RwLock::default(),
+            cache: Cache::default(),
+        }
+    }
+
+    /// The directory templates are loaded from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Registers a template filter; see [`App::add_filter`](crate::App::add_filter).
+    pub fn add_filter<F>(&self, name: &str, filter: F)
+    where
+        F: Fn(&Value, &[Value]) -> Result<Value, Error> + Send + Sync + 'static,
+    {
+        self.insert_filter(name, Arc::new(filter));
+    }
+
+    /// Renders a template like
+    /// [`render_with_context`](super::render_with_context), with the filters
+    /// of this environment.
+    pub fn render(&self, file: &str, context: &Context) -> Result<String, Error> {
+        super::render_page(self, file, context, super::render::Missing::Fail)
+    }
+
+    /// Discards the parsed templates cached by this environment, so each
+    /// template is read from disk again the next time it is rendered.
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
+    /// Discards the cached templates read from `path`, or from files under
+    /// it if it is a directory. Other templates stay cached.
+    ///
+    /// In dev mode the file watcher does this for every changed file.
+    pub fn invalidate(&self, path: impl AsRef<Path>) {
+        self.cache.invalidate(path.as_ref());
+    }
+
+    /// Returns the parsed template `file`, or `None` if it does not exist.
+    pub(crate) fn template(&self, file: &str) -> Result<Option<Arc<Template>>, Error> {
+        self.cache.get(&self.root, file)
+    }
+
+    fn insert_filter(&self, name: &str, filter: FilterFn) {
+        let mut filters = self.filters.write().unwrap_or_else(|e| e.into_inner());
+        Arc::make_mut(&mut filters).insert(name, filter);
+    }
+
+    /// Adds the filters of `other` that are not registered here.
+    pub(crate) fn merge_filters(&self, other: &Environment) {
+        let other = other.filters();
+        let mut filters = self.filters.write().unwrap_or_else(|e| e.into_inner());
+        Arc::make_mut(&mut filters).merge(&other);
+    }
+
+    pub(crate) fn filters(&self) -> Arc<Filters> {
+        self.filters
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Whether this is the environment used outside of any app's requests.
+    pub(crate) fn is_default(&self) -> bool {
+        std::ptr::eq(self, &**default())
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The environment used outside of any app's requests.
+pub(crate) fn default() -> &'static Arc<Environment> {
+    static DEFAULT: OnceLock<Arc<Environment>> = OnceLock::new();
+    DEFAULT.get_or_init(Arc::default)
+}
+
+/// Runs `fut` with `env` used for templates rendered inside it.
+pub(crate) async fn scope<F: Future>(env: Arc<Environment>, fut: F) -> F::Output {
+    CURRENT.scope(env, fut).await
+}
+
+/// Returns the environment of the app handling the current request, or the
+/// default environment outside of requests.
+pub(crate) fn current() -> Arc<Environment> {
+    CURRENT
+        .try_with(Arc::clone)
+        .unwrap_or_else(|_| default().clone())
+}
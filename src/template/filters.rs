@@ -0,0 +1,431 @@
+//! Built-in template filters and the registry of application filters.
+
+use super::value::Value;
+use crate::error::Error;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// A filter function: takes the value and the filter's arguments.
+pub(crate) type FilterFn = Arc<dyn Fn(&Value, &[Value]) -> Result<Value, Error> + Send + Sync>;
+
+/// Filters registered on an app with `App::add_filter`.
+#[derive(Clone, Default)]
+pub(crate) struct Filters {
+    custom: HashMap<String, FilterFn>,
+}
+
+impl Filters {
+    pub fn insert(&mut self, name: &str, filter: FilterFn) {
+        self.custom.insert(name.to_string(), filter);
+    }
+
+    /// Adds the filters of `other` that are not registered here.
+    pub fn merge(&mut self, other: &Filters) {
+        for (name, filter) in &other.custom {
+            self.custom
+                .entry(name.clone())
+                .or_insert_with(|| filter.clone());
+        }
+    }
+
+    /// Applies the filter `name`, preferring a registered filter over a
+    /// built-in one. Returns `None` if there is no such filter.
+    pub fn apply(&self, name: &str, value: &Value, args: &[Value]) -> Option<Result<Value, Error>> {
+        if let Some(filter) = self.custom.get(name) {
+            return Some(filter(value, args));
+        }
+        let builtin: fn(&Value, &[Value]) -> Result<Value, Error> = match name {
+            "upper" => |v, _| Ok(v.to_string().to_uppercase().into()),
+            "lower" => |v, _| Ok(v.to_string().to_lowercase().into()),
+            "trim" => |v, _| Ok(v.to_string().trim().into()),
+            "default" => default,
+            "length" => length,
+            "join" => join,
+            "truncate" => truncate,
+            "format_number" => format_number,
+            "date" => date,
+            "urlencode" => urlencode,
+            "json" => |v, _| Ok(to_json(v).into()),
+            _ => return None,
+        };
+        Some(builtin(value, args))
+    }
+}
+
+/// The argument at `index`, if given.
+fn arg(args: &[Value], index: usize) -> Option<&Value> {
+    args.get(index)
+}
+
+/// The argument at `index` as a count, or `default` if it is not given.
+fn count_arg(args: &[Value], index: usize, default: usize) -> Result<usize, Error> {
+    match arg(args, index) {
+        None => Ok(default),
+        Some(value) => value
+            .as_f64()
+            .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+            .map(|n| n as usize)
+            .ok_or_else(|| Error::internal(format!("expected a count, found {}", value.kind()))),
+    }
+}
+
+/// `default(fallback)`: the fallback for missing or empty values.
+fn default(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    Ok(match value {
+        Value::Null => arg(args, 0).cloned().unwrap_or_default(),
+        Value::Str(s) if s.is_empty() => arg(args, 0).cloned().unwrap_or_default(),
+        _ => value.clone(),
+    })
+}
+
+/// `length`: the number of characters, items or entries.
+fn length(value: &Value, _: &[Value]) -> Result<Value, Error> {
+    Ok(match value {
+        Value::Null => 0.into(),
+        Value::Str(s) => s.chars().count().into(),
+        Value::List(items) => items.len().into(),
+        Value::Map(map) => map.len().into(),
+        other => {
+            return Err(Error::internal(format!(
+                "cannot take the length of {}",
+                other.kind()
+            )))
+        }
+    })
+}
+
+/// `join(separator)`: the items of a list, separated by `", "` by default.
+fn join(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    let separator = arg(args, 0).map_or(", ".to_string(), Value::to_string);
+    Ok(match value {
+        Value::List(items) => items
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join(&separator)
+            .into(),
+        other => other.to_string().into(),
+    })
+}
+
+/// `truncate(length, end)`: shortens text to `length` characters followed by
+/// `end` (`"..."` by default).
+fn truncate(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    let length = count_arg(args, 0, 255)?;
+    let text = value.to_string();
+    if text.chars().count() <= length {
+        return Ok(text.into());
+    }
+    let end = arg(args, 1).map_or("...".to_string(), Value::to_string);
+    let cut: String = text.chars().take(length).collect();
+    Ok(format!("{}{}", cut.trim_end(), end).into())
+}
+
+/// `format_number(decimals)`: a number with thousands separators, rounded
+/// to `decimals` places if given. Numeric strings such as `"+123"` or
+/// `"1e5"` are formatted from the number they hold.
+fn format_number(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    if *value == Value::Null {
+        return Ok(Value::Null);
+    }
+    let number = value
+        .as_f64()
+        .filter(|n| n.is_finite())
+        .ok_or_else(|| Error::internal(format!("expected a number, found {}", value.kind())))?;
+    let text = match (arg(args, 0), value) {
+        (Some(_), _) => format!("{:.*}", count_arg(args, 0, 0)?, number),
+        (None, Value::Int(n)) => n.to_string(),
+        (None, _) => number.to_string(),
+    };
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", text.as_str()),
+    };
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (digits, None),
+    };
+    let mut out = sign.to_string();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        out.push('.');
+        out.push_str(fraction);
+    }
+    Ok(out.into())
+}
+
+/// `urlencode`: percent-encodes text, or a map as a query string.
+fn urlencode(value: &Value, _: &[Value]) -> Result<Value, Error> {
+    let encode = |v: &Value| urlencoding::encode(&v.to_string()).into_owned();
+    Ok(match value {
+        Value::Map(map) => map
+            .iter()
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), encode(v)))
+            .collect::<Vec<_>>()
+            .join("&")
+            .into(),
+        other => encode(other).into(),
+    })
+}
+
+/// Encodes a value as JSON, escaping `<`, `>`, `&` and `'` so the result
+/// cannot close a `<script>` element.
+fn to_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_json(&mut out, value);
+    out
+}
+
+fn write_json(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => write!(out, "{}", b).unwrap(),
+        Value::Int(n) => write!(out, "{}", n).unwrap(),
+        Value::Float(n) if n.is_finite() => write!(out, "{:?}", n).unwrap(),
+        Value::Float(_) => out.push_str("null"),
+        Value::Str(s) => write_json_str(out, s),
+        Value::List(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(out, item);
+            }
+            out.push(']');
+        }
+        Value::Map(map) => {
+            out.push('{');
+            for (i, (key, item)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json_str(out, key);
+                out.push(':');
+                write_json(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_json_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '<' | '>' | '&' | '\'' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// `date(format)`: formats a Unix timestamp (in UTC) or an ISO 8601 date or
+/// date-time string (as written, without time zone conversion) with
+/// strftime-style directives; the format defaults to `%Y-%m-%d`.
+fn date(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    if *value == Value::Null {
+        return Ok(Value::Null);
+    }
+    let format = arg(args, 0).map_or("%Y-%m-%d".to_string(), Value::to_string);
+    let time = match value {
+        Value::Int(secs) => DateTime::from_timestamp(*secs),
+        Value::Float(secs) => DateTime::from_timestamp(secs.floor() as i64),
+        Value::Str(s) => DateTime::parse(s.trim())
+            .ok_or_else(|| Error::internal(format!("'{}' is not a date", s)))?,
+        other => {
+            return Err(Error::internal(format!(
+                "expected a date, found {}",
+                other.kind()
+            )))
+        }
+    };
+    time.format(&format).map(Value::Str)
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// A calendar date and time of day.
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl DateTime {
+    fn from_timestamp(secs: i64) -> Self {
+        let (days, secs) = (secs.div_euclid(86_400), secs.rem_euclid(86_400) as u32);
+        let (year, month, day) = civil_from_days(days);
+        DateTime {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            minute: secs / 60 % 60,
+            second: secs % 60,
+        }
+    }
+
+    /// Parses `YYYY-MM-DD`, optionally followed by `T` or a space and
+    /// `HH:MM[:SS[.fraction]]` and a time zone, which is ignored.
+    fn parse(s: &str) -> Option<Self> {
+        let field = |s: &str, range: std::ops::RangeInclusive<u32>| {
+            s.parse().ok().filter(|n| range.contains(n))
+        };
+        let (date, time) = match s.split_once(['T', ' ']) {
+            Some((date, time)) => (date, Some(time)),
+            None => (s, None),
+        };
+        let mut parts = date.splitn(3, '-');
+        let year = parts.next()?;
+        if year.len() != 4 {
+            return None;
+        }
+        let mut parsed = DateTime {
+            year: year.parse().ok()?,
+            month: field(parts.next()?, 1..=12)?,
+            day: field(parts.next()?, 1..=31)?,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        };
+        if let Some(time) = time {
+            let time = time
+                .split(['Z', 'z', '+', '-', '.'])
+                .next()
+                .unwrap_or_default();
+            let mut parts = time.split(':');
+            parsed.hour = field(parts.next()?, 0..=23)?;
+            parsed.minute = field(parts.next()?, 0..=59)?;
+            parsed.second = match parts.next() {
+                Some(second) => field(second, 0..=60)?,
+                None => 0,
+            };
+        }
+        Some(parsed)
+    }
+
+    fn format(&self, format: &str) -> Result<String, Error> {
+        let mut out = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            let month = MONTHS[self.month as usize - 1];
+            let weekday = WEEKDAYS[self.weekday()];
+            match chars.next() {
+                Some('Y') => write!(out, "{}", self.year),
+                Some('y') => write!(out, "{:02}", self.year.rem_euclid(100)),
+                Some('m') => write!(out, "{:02}", self.month),
+                Some('d') => write!(out, "{:02}", self.day),
+                Some('e') => write!(out, "{:2}", self.day),
+                Some('H') => write!(out, "{:02}", self.hour),
+                Some('I') => write!(out, "{:02}", (self.hour + 11) % 12 + 1),
+                Some('p') => out.write_str(if self.hour < 12 { "AM" } else { "PM" }),
+                Some('M') => write!(out, "{:02}", self.minute),
+                Some('S') => write!(out, "{:02}", self.second),
+                Some('B') => out.write_str(month),
+                Some('b') => out.write_str(&month[..3]),
+                Some('A') => out.write_str(weekday),
+                Some('a') => out.write_str(&weekday[..3]),
+                Some('j') => write!(out, "{:03}", self.day_of_year()),
+                Some('F') => write!(out, "{}-{:02}-{:02}", self.year, self.month, self.day),
+                Some('T') => write!(
+                    out,
+                    "{:02}:{:02}:{:02}",
+                    self.hour, self.minute, self.second
+                ),
+                Some('%') => out.write_str("%"),
+                Some(other) => {
+                    return Err(Error::internal(format!(
+                        "unknown date directive '%{}'",
+                        other
+                    )))
+                }
+                None => return Err(Error::internal("date format ends with '%'")),
+            }
+            .unwrap();
+        }
+        Ok(out)
+    }
+
+    fn days(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day)
+    }
+
+    /// 0 for Sunday through 6 for Saturday.
+    fn weekday(&self) -> usize {
+        // 1970-01-01 was a Thursday.
+        (self.days() + 4).rem_euclid(7) as usize
+    }
+
+    fn day_of_year(&self) -> i64 {
+        self.days() - days_from_civil(self.year, 1, 1) + 1
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The proleptic Gregorian date `days` days after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,733 @@
+//! Parsing of template source into a tree of [`Node`]s.
+
+use super::value::Value;
+use crate::error::Error;
+use std::sync::Arc;
+
+/// A parsed template.
+#[derive(Debug)]
+pub(crate) struct Template {
+    pub name: String,
+    pub nodes: Vec<Node>,
+}
+
+impl Template {
+    /// Returns the route name and parameter names of every `url_for` call.
+    pub fn url_for_calls(&self) -> Vec<(&str, Vec<&str>)> {
+        let mut calls = Vec::new();
+        visit_exprs(&self.nodes, &mut |expr| {
+            if let Expr::UrlFor { name, args, .. } = expr {
+                calls.push((
+                    name.as_str(),
+                    args.iter().map(|(k, _)| k.as_str()).collect(),
+                ));
+            }
+        });
+        calls
+    }
+}
+
+/// Calls `f` on every expression in `nodes`, including nested ones.
+fn visit_exprs<'a>(nodes: &'a [Node], f: &mut impl FnMut(&'a Expr)) {
+    fn visit<'a>(expr: &'a Expr, f: &mut impl FnMut(&'a Expr)) {
+        f(expr);
+        match expr {
+            Expr::Not(inner) => visit(inner, f),
+            Expr::And(left, right)
+            | Expr::Or(left, right)
+            | Expr::Compare(left, _, right)
+            | Expr::Lookup(left, right) => {
+                visit(left, f);
+                visit(right, f);
+            }
+            Expr::UrlFor { args, .. } => args.iter().for_each(|(_, arg)| visit(arg, f)),
+            Expr::Literal(_) | Expr::Var(_) => {}
+        }
+    }
+    for node in nodes {
+        match node {
+            Node::Output { expr, filters, .. } => {
+                visit(expr, f);
+                filters
+                    .iter()
+                    .flat_map(|filter| &filter.args)
+                    .for_each(|arg| visit(arg, f));
+            }
+            Node::If {
+                branches,
+                otherwise,
+            } => {
+                for (condition, body) in branches {
+                    visit(condition, f);
+                    visit_exprs(body, f);
+                }
+                visit_exprs(otherwise, f);
+            }
+            Node::For {
+                iterable,
+                body,
+                empty,
+                ..
+            } => {
+                visit(iterable, f);
+                visit_exprs(body, f);
+                visit_exprs(empty, f);
+            }
+            Node::Block { body, .. } => visit_exprs(body, f),
+            Node::Text(_) | Node::Include { .. } | Node::Extends { .. } => {}
+        }
+    }
+}
+
+/// A piece of a template.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Node {
+    /// Literal text.
+    Text(String),
+    /// `{{ expr | filter }}`, or `{{{ expr }}}` when `raw`.
+    Output {
+        expr: Expr,
+        filters: Vec<Filter>,
+        raw: bool,
+        line: usize,
+    },
+    /// `{% if %}` with its `{% elif %}` branches and `{% else %}` body.
+    If {
+        branches: Vec<(Expr, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+    /// `{% for var in iterable %}`, with the `{% empty %}` body.
+    For {
+        var: String,
+        iterable: Expr,
+        body: Vec<Node>,
+        empty: Vec<Node>,
+        line: usize,
+    },
+    /// `{% include 'file' %}`
+    Include { file: String, line: usize },
+    /// `{% extends 'file' %}`
+    Extends { file: String, line: usize },
+    /// `{% block name %}`, overridable by templates extending this one.
+    Block { name: String, body: Arc<[Node]> },
+}
+
+/// A filter applied to an output, as in `{{ name | safe }}`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Filter {
+    pub name: String,
+    pub args: Vec<Expr>,
+}
+
+/// An expression inside `{{ }}` or a tag.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    Literal(Value),
+    Var(String),
+    /// `value.field`, `value['key']` or `value[index]`.
+    Lookup(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+    /// `url_for('name', key=value, ...)`
+    UrlFor {
+        name: String,
+        args: Vec<(String, Expr)>,
+        line: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    NotIn,
+}
+
+/// Parses the source of the template `name`.
+pub(crate) fn parse(name: &str, source: &str) -> Result<Template, Error> {
+    let pieces = lex(name, source)?;
+    let mut parser = Parser {
+        name,
+        pieces: pieces.into_iter(),
+    };
+    let (nodes, end) = parser.parse_nodes(&[])?;
+    debug_assert!(end.is_none());
+    Ok(Template {
+        name: name.to_string(),
+        nodes,
+    })
+}
+
+/// Builds the error for a syntax error at `line` of template `name`.
+pub(crate) fn syntax_error(name: &str, line: usize, message: impl std::fmt::Display) -> Error {
+    Error::internal(format!("Template '{}' line {}: {}", name, line, message))
+}
+
+/// A top-level piece of template source.
+#[derive(Debug)]
+enum Piece<'a> {
+    Text(&'a str),
+    Output {
+        source: &'a str,
+        raw: bool,
+        line: usize,
+    },
+    Tag {
+        source: &'a str,
+        line: usize,
+    },
+}
+
+/// Splits template source into text, `{{ }}`, `{{{ }}}` and `{% %}` pieces,
+/// dropping `{# comments #}`. Everything between `{% raw %}` and
+/// `{% endraw %}` is text.
+fn lex<'a>(name: &str, source: &'a str) -> Result<Vec<Piece<'a>>, Error> {
+    let mut pieces = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start..];
+        let (open, close) = if after.starts_with("{{{") {
+            ("{{{", "}}}")
+        } else if after.starts_with("{{") {
+            ("{{", "}}")
+        } else if after.starts_with("{%") {
+            ("{%", "%}")
+        } else if after.starts_with("{#") {
+            ("{#", "#}")
+        } else {
+            // A lone brace is text; keep scanning after it.
+            pieces.push(Piece::Text(&rest[..start + 1]));
+            line += rest[..start + 1].matches('\n').count();
+            rest = &rest[start + 1..];
+            continue;
+        };
+        if start > 0 {
+            pieces.push(Piece::Text(&rest[..start]));
+            line += rest[..start].matches('\n').count();
+        }
+        let inner_start = start + open.len();
+        let Some(len) = rest[inner_start..].find(close) else {
+            return Err(syntax_error(name, line, format!("unclosed '{}'", open)));
+        };
+        let inner = &rest[inner_start..inner_start + len];
+        match open {
+            "{{{" => pieces.push(Piece::Output {
+                source: inner,
+                raw: true,
+                line,
+            }),
+            "{{" => pieces.push(Piece::Output {
+                source: inner,
+                raw: false,
+                line,
+            }),
+            "{%" if inner.trim() == "raw" => {
+                let body = &rest[inner_start + len + close.len()..];
+                let Some((text, end)) = find_endraw(body) else {
+                    return Err(syntax_error(name, line, "missing {% endraw %}"));
+                };
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(text));
+                }
+                line += rest[start..rest.len() - body.len() + end]
+                    .matches('\n')
+                    .count();
+                rest = &body[end..];
+                continue;
+            }
+            "{%" => pieces.push(Piece::Tag {
+                source: inner,
+                line,
+            }),
+            _ => {}
+        }
+        line += inner.matches('\n').count();
+        rest = &rest[inner_start + len + close.len()..];
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    Ok(pieces)
+}
+
+/// Finds the `{% endraw %}` tag in `source`, returning the text before it
+/// and the position just after it.
+fn find_endraw(source: &str) -> Option<(&str, usize)> {
+    let mut from = 0;
+    while let Some(start) = source[from..].find("{%") {
+        let start = from + start;
+        let len = source[start + 2..].find("%}")?;
+        if source[start + 2..start + 2 + len].trim() == "endraw" {
+            return Some((&source[..start], start + len + 4));
+        }
+        from = start + 2;
+    }
+    None
+}
+
+struct Parser<'a> {
+    name: &'a str,
+    pieces: std::vec::IntoIter<Piece<'a>>,
+}
+
+/// The tag that ended a run of nodes, with its remaining tokens.
+type EndTag = (String, Tokens);
+
+impl<'a> Parser<'a> {
+    /// Parses nodes until one of the `end` tags, returning the nodes and the
+    /// tag that ended them (`None` at the end of the template).
+    fn parse_nodes(&mut self, end: &[&str]) -> Result<(Vec<Node>, Option<EndTag>), Error> {
+        let mut nodes: Vec<Node> = Vec::new();
+        while let Some(piece) = self.pieces.next() {
+            match piece {
+                Piece::Text(text) => match nodes.last_mut() {
+                    Some(Node::Text(previous)) => previous.push_str(text),
+                    _ => nodes.push(Node::Text(text.to_string())),
+                },
+                Piece::Output { source, raw, line } => {
+                    let mut tokens = Tokens::new(self.name, source, line)?;
+                    let expr = tokens.parse_expr()?;
+                    let filters = tokens.parse_filters()?;
+                    tokens.expect_end()?;
+                    nodes.push(Node::Output {
+                        expr,
+                        filters,
+                        raw,
+                        line,
+                    });
+                }
+                Piece::Tag { source, line } => {
+                    let mut tokens = Tokens::new(self.name, source, line)?;
+                    let keyword = tokens.expect_ident("a tag name")?;
+                    if end.contains(&keyword.as_str()) {
+                        return Ok((nodes, Some((keyword, tokens))));
+                    }
+                    nodes.push(self.parse_tag(&keyword, tokens, line)?);
+                }
+            }
+        }
+        match end.first() {
+            Some(expected) => Err(self.error_at_end(format!("missing {{% {} %}}", expected))),
+            None => Ok((nodes, None)),
+        }
+    }
+
+    fn parse_tag(&mut self, keyword: &str, mut tokens: Tokens, line: usize) -> Result<Node, Error> {
+        match keyword {
+            "if" => {
+                let mut branches = Vec::new();
+                let mut condition = tokens.parse_expr()?;
+                tokens.expect_end()?;
+                loop {
+                    let (body, end) = self.parse_nodes(&["endif", "elif", "else"])?;
+                    branches.push((condition, body));
+                    let (end, mut tokens) = end.expect("parse_nodes returns the end tag");
+                    match end.as_str() {
+                        "elif" => {
+                            condition = tokens.parse_expr()?;
+                            tokens.expect_end()?;
+                        }
+                        "else" => {
+                            tokens.expect_end()?;
+                            let (otherwise, end) = self.parse_nodes(&["endif"])?;
+                            end.expect("parse_nodes returns the end tag")
+                                .1
+                                .expect_end()?;
+                            return Ok(Node::If {
+                                branches,
+                                otherwise,
+                            });
+                        }
+                        _ => {
+                            tokens.expect_end()?;
+                            return Ok(Node::If {
+                                branches,
+                                otherwise: Vec::new(),
+                            });
+                        }
+                    }
+                }
+            }
+            "for" => {
+                let var = tokens.expect_ident("a loop variable")?;
+                tokens.expect_keyword("in")?;
+                let iterable = tokens.parse_expr()?;
+                tokens.expect_end()?;
+                let (body, end) = self.parse_nodes(&["endfor", "empty"])?;
+                let (end, mut tokens) = end.expect("parse_nodes returns the end tag");
+                tokens.expect_end()?;
+                let empty = match end.as_str() {
+                    "empty" => {
+                        let (empty, end) = self.parse_nodes(&["endfor"])?;
+                        end.expect("parse_nodes returns the end tag")
+                            .1
+                            .expect_end()?;
+                        empty
+                    }
+                    _ => Vec::new(),
+                };
+                Ok(Node::For {
+                    var,
+                    iterable,
+                    body,
+                    empty,
+                    line,
+                })
+            }
+            "block" => {
+                let name = tokens.expect_ident("a block name")?;
+                tokens.expect_end()?;
+                let (body, end) = self.parse_nodes(&["endblock"])?;
+                let mut tokens = end.expect("parse_nodes returns the end tag").1;
+                if let Some(Token::Ident(end_name)) = tokens.peek().cloned() {
+                    if end_name != name {
+                        return Err(tokens.error(format!(
+                            "{{% endblock {} %}} closes block '{}'",
+                            end_name, name
+                        )));
+                    }
+                    tokens.next();
+                }
+                tokens.expect_end()?;
+                Ok(Node::Block {
+                    name,
+                    body: body.into(),
+                })
+            }
+            "include" | "extends" => {
+                let file = match tokens.next() {
+                    Some(Token::Str(file)) => file,
+                    _ => {
+                        return Err(tokens
+                            .error(format!("expected a quoted file name after '{}'", keyword)))
+                    }
+                };
+                tokens.expect_end()?;
+                Ok(match keyword {
+                    "include" => Node::Include { file, line },
+                    _ => Node::Extends { file, line },
+                })
+            }
+            "elif" | "else" | "endif" | "endfor" | "empty" | "endblock" => Err(syntax_error(
+                self.name,
+                line,
+                format!("unexpected {{% {} %}}", keyword),
+            )),
+            _ => Err(syntax_error(
+                self.name,
+                line,
+                format!("unknown tag '{}'", keyword),
+            )),
+        }
+    }
+
+    fn error_at_end(&self, message: String) -> Error {
+        Error::internal(format!("Template '{}': {}", self.name, message))
+    }
+}
+
+/// A token inside `{{ }}` or `{% %}`.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 18] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "(", ")", "[", "]", ",", "=", ".", "|", "!", "-",
+];
+
+/// The tokens of one tag or output, with a cursor.
+struct Tokens {
+    name: String,
+    line: usize,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Tokens {
+    fn new(name: &str, source: &str, line: usize) -> Result<Self, Error> {
+        let error = |message: String| syntax_error(name, line, message);
+        let mut tokens = Vec::new();
+        let mut chars = source.char_indices().peekable();
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '\'' || c == '"' {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => value.extend(chars.next().map(|(_, c)| c)),
+                        Some((_, q)) if q == c => break,
+                        Some((_, other)) => value.push(other),
+                        None => return Err(error("unterminated string".to_string())),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            } else if c.is_ascii_digit() {
+                let mut end = i;
+                while let Some(&(j, d)) = chars.peek() {
+                    if d.is_ascii_digit()
+                        || d == '.' && source[j + 1..].starts_with(|n: char| n.is_ascii_digit())
+                    {
+                        end = j + d.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let number = &source[i..end];
+                tokens.push(match number.contains('.') {
+                    true => Token::Float(
+                        number
+                            .parse()
+                            .map_err(|_| error(format!("invalid number '{}'", number)))?,
+                    ),
+                    false => Token::Int(
+                        number
+                            .parse()
+                            .map_err(|_| error(format!("invalid number '{}'", number)))?,
+                    ),
+                });
+            } else if c.is_alphabetic() || c == '_' {
+                let mut end = i;
+                while let Some(&(j, d)) = chars.peek() {
+                    if d.is_alphanumeric() || d == '_' {
+                        end = j + d.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(source[i..end].to_string()));
+            } else {
+                let op = OPERATORS
+                    .iter()
+                    .find(|op| source[i..].starts_with(**op))
+                    .ok_or_else(|| error(format!("unexpected character '{}'", c)))?;
+                for _ in 0..op.len() {
+                    chars.next();
+                }
+                tokens.push(Token::Op(op));
+            }
+        }
+        Ok(Tokens {
+            name: name.to_string(),
+            line,
+            tokens,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> Error {
+        syntax_error(&self.name, self.line, message)
+    }
+
+    /// Consumes the operator `op` if it is next.
+    fn eat_op(&mut self, op: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Op(o)) if *o == op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Consumes the keyword `word` if it is next.
+    fn eat_keyword(&mut self, word: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Ident(w)) if w == word);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, word: &str) -> Result<(), Error> {
+        match self.eat_keyword(word) {
+            true => Ok(()),
+            false => Err(self.error(format!("expected '{}'", word))),
+        }
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<(), Error> {
+        match self.eat_op(op) {
+            true => Ok(()),
+            false => Err(self.error(format!("expected '{}'", op))),
+        }
+    }
+
+    fn expect_ident(&mut self, what: &str) -> Result<String, Error> {
+        match self.next() {
+            Some(Token::Ident(ident)) => Ok(ident),
+            _ => Err(self.error(format!("expected {}", what))),
+        }
+    }
+
+    fn expect_end(&mut self) -> Result<(), Error> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => Err(self.error(format!("unexpected {}", describe(token)))),
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.parse_and()?;
+        while self.eat_keyword("or") || self.eat_op("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.parse_not()?;
+        while self.eat_keyword("and") || self.eat_op("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, Error> {
+        match self.eat_keyword("not") || self.eat_op("!") {
+            true => Ok(Expr::Not(Box::new(self.parse_not()?))),
+            false => self.parse_comparison(),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, Error> {
+        let left = self.parse_primary()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => CompareOp::Eq,
+            Some(Token::Op("!=")) => CompareOp::Ne,
+            Some(Token::Op("<")) => CompareOp::Lt,
+            Some(Token::Op("<=")) => CompareOp::Le,
+            Some(Token::Op(">")) => CompareOp::Gt,
+            Some(Token::Op(">=")) => CompareOp::Ge,
+            Some(Token::Ident(word)) if word == "in" => CompareOp::In,
+            Some(Token::Ident(word))
+                if word == "not"
+                    && matches!(self.tokens.get(self.pos + 1), Some(Token::Ident(w)) if w == "in") =>
+            {
+                self.pos += 1;
+                CompareOp::NotIn
+            }
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.parse_primary()?;
+        Ok(Expr::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, Error> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::Str(s))),
+            Some(Token::Int(n)) => Ok(Expr::Literal(Value::Int(n))),
+            Some(Token::Float(n)) => Ok(Expr::Literal(Value::Float(n))),
+            Some(Token::Op("-")) => match self.next() {
+                Some(Token::Int(n)) => Ok(Expr::Literal(Value::Int(-n))),
+                Some(Token::Float(n)) => Ok(Expr::Literal(Value::Float(-n))),
+                _ => Err(self.error("expected a number after '-'")),
+            },
+            Some(Token::Op("(")) => {
+                let expr = self.parse_expr()?;
+                self.expect_op(")")?;
+                Ok(expr)
+            }
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "none" => Ok(Expr::Literal(Value::Null)),
+                "url_for" if self.eat_op("(") => self.parse_url_for(),
+                _ => self.parse_lookups(Expr::Var(ident)),
+            },
+            Some(token) => Err(self.error(format!("unexpected {}", describe(&token)))),
+            None => Err(self.error("expected an expression")),
+        }
+    }
+
+    /// Parses any `.field` and `[key]` lookups following a variable.
+    fn parse_lookups(&mut self, mut expr: Expr) -> Result<Expr, Error> {
+        loop {
+            let key = if self.eat_op(".") {
+                Expr::Literal(Value::Str(self.expect_ident("a field name after '.'")?))
+            } else if self.eat_op("[") {
+                let key = self.parse_expr()?;
+                self.expect_op("]")?;
+                key
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Lookup(Box::new(expr), Box::new(key));
+        }
+    }
+
+    /// Parses the arguments of `url_for(`, up to and including `)`.
+    fn parse_url_for(&mut self) -> Result<Expr, Error> {
+        let name = match self.next() {
+            Some(Token::Str(name)) => name,
+            _ => return Err(self.error("url_for expects a quoted route name")),
+        };
+        let mut args = Vec::new();
+        while self.eat_op(",") {
+            let key = self.expect_ident("a parameter name")?;
+            self.expect_op("=")?;
+            args.push((key, self.parse_primary()?));
+        }
+        self.expect_op(")")?;
+        Ok(Expr::UrlFor {
+            name,
+            args,
+            line: self.line,
+        })
+    }
+
+    /// Parses `| name` and `| name(args)` filters.
+    fn parse_filters(&mut self) -> Result<Vec<Filter>, Error> {
+        let mut filters = Vec::new();
+        while self.eat_op("|") {
+            let name = self.expect_ident("a filter name")?;
+            let mut args = Vec::new();
+            if self.eat_op("(") && !self.eat_op(")") {
+                loop {
+                    args.push(self.parse_expr()?);
+                    if self.eat_op(")") {
+                        break;
+                    }
+                    self.expect_op(",")?;
+                }
+            }
+            filters.push(Filter { name, args });
+        }
+        Ok(filters)
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(ident) => format!("'{}'", ident),
+        Token::Str(s) => format!("string '{}'", s),
+        Token::Int(n) => format!("number {}", n),
+        Token::Float(n) => format!("number {}", n),
+        Token::Op(op) => format!("'{}'", op),
+    }
+}
@@ -0,0 +1,336 @@
+//! Rendering of parsed templates.
+
+use super::environment::Environment;
+use super::filters::Filters;
+use super::parser::{self, CompareOp, Expr, Node, Template};
+use super::value::{Context, Value};
+use super::{escape_html, escape_unquoted_attribute, HtmlState};
+use crate::error::Error;
+use crate::url::RouteNames;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// How deeply includes and `extends` may nest, to stop cycles.
+const MAX_DEPTH: usize = 32;
+
+/// What to do when a template, include or base template is missing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Missing {
+    /// Fail rendering with a "not found" error.
+    Fail,
+    /// Render the "not found" message in place of the missing template.
+    Inline,
+}
+
+/// Renders the template `file` with `context` in `env`.
+pub(crate) fn render(
+    env: &Environment,
+    file: &str,
+    context: &Context,
+    missing: Missing,
+) -> Result<String, Error> {
+    let mut renderer = Renderer {
+        env,
+        context,
+        missing,
+        scopes: Vec::new(),
+        blocks: HashMap::new(),
+        names: crate::url::current().unwrap_or_default(),
+        filters: env.filters(),
+        state: HtmlState::Text,
+        out: String::new(),
+        depth: 0,
+    };
+    renderer.render_file(file)?;
+    Ok(renderer.out)
+}
+
+struct Renderer<'a> {
+    env: &'a Environment,
+    context: &'a Context,
+    missing: Missing,
+    /// Variables set by enclosing `for` loops, innermost last.
+    scopes: Vec<(String, Value)>,
+    /// Block overrides from the templates extending the one being rendered,
+    /// with the template each one comes from.
+    blocks: HashMap<String, (Arc<Template>, Arc<[Node]>)>,
+    names: Arc<RouteNames>,
+    filters: Arc<Filters>,
+    state: HtmlState,
+    out: String,
+    depth: usize,
+}
+
+impl Renderer<'_> {
+    fn render_file(&mut self, file: &str) -> Result<(), Error> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(Error::internal(format!(
+                "Template '{}' includes or extends itself",
+                file
+            )));
+        }
+        let Some(template) = self.env.template(file)? else {
+            let error = Error::internal(format!("Template '{}' not found", file));
+            if self.missing == Missing::Fail {
+                return Err(error);
+            }
+            self.push_text(&format!("<h1>{}</h1>", error));
+            self.depth -= 1;
+            return Ok(());
+        };
+        let extends = template.nodes.iter().find_map(|node| match node {
+            Node::Extends { file, .. } => Some(file.clone()),
+            _ => None,
+        });
+        match extends {
+            Some(base) => {
+                collect_blocks(&template, &template.nodes, &mut self.blocks);
+                self.render_file(&base)?;
+            }
+            None => self.render_nodes(&template, &template.nodes)?,
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn render_nodes(&mut self, template: &Template, nodes: &[Node]) -> Result<(), Error> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.push_text(text),
+                Node::Output {
+                    expr,
+                    filters,
+                    raw,
+                    line,
+                } => {
+                    let mut safe = *raw || matches!(expr, Expr::UrlFor { .. });
+                    let mut value = self.eval(template, expr)?;
+                    for filter in filters {
+                        if filter.name == "safe" {
+                            safe = true;
+                            continue;
+                        }
+                        let args = filter
+                            .args
+                            .iter()
+                            .map(|arg| self.eval(template, arg))
+                            .collect::<Result<Vec<_>, Error>>()?;
+                        let error = |message| parser::syntax_error(&template.name, *line, message);
+                        value = match self.filters.apply(&filter.name, &value, &args) {
+                            Some(Ok(value)) => value,
+                            Some(Err(e)) => {
+                                return Err(error(format!(
+                                    "filter '{}': {}",
+                                    filter.name,
+                                    e.message()
+                                )))
+                            }
+                            None => return Err(error(self.unknown_filter(&filter.name))),
+                        };
+                    }
+                    self.push_value(&value.to_string(), !safe && escapes(&template.name));
+                }
+                Node::If {
+                    branches,
+                    otherwise,
+                } => {
+                    let mut body = otherwise;
+                    for (condition, branch) in branches {
+                        if self.eval(template, condition)?.is_truthy() {
+                            body = branch;
+                            break;
+                        }
+                    }
+                    self.render_nodes(template, body)?;
+                }
+                Node::For {
+                    var,
+                    iterable,
+                    body,
+                    empty,
+                    line,
+                } => {
+                    let items = match self.eval(template, iterable)? {
+                        Value::Null => Vec::new(),
+                        Value::List(items) => items,
+                        Value::Map(map) => map.into_keys().map(Value::Str).collect(),
+                        other => {
+                            return Err(parser::syntax_error(
+                                &template.name,
+                                *line,
+                                format!("cannot loop over {}", other.kind()),
+                            ))
+                        }
+                    };
+                    if items.is_empty() {
+                        self.render_nodes(template, empty)?;
+                    }
+                    let length = items.len();
+                    for (index, item) in items.into_iter().enumerate() {
+                        let info = BTreeMap::from([
+                            ("index".to_string(), Value::from(index + 1)),
+                            ("index0".to_string(), Value::from(index)),
+                            ("revindex".to_string(), Value::from(length - index)),
+                            ("first".to_string(), Value::Bool(index == 0)),
+                            ("last".to_string(), Value::Bool(index + 1 == length)),
+                            ("length".to_string(), Value::from(length)),
+                        ]);
+                        self.scopes.push(("loop".to_string(), Value::Map(info)));
+                        self.scopes.push((var.clone(), item));
+                        let result = self.render_nodes(template, body);
+                        self.scopes.truncate(self.scopes.len() - 2);
+                        result?;
+                    }
+                }
+                Node::Include { file, .. } => {
+                    let blocks = std::mem::take(&mut self.blocks);
+                    let result = self.render_file(file);
+                    self.blocks = blocks;
+                    result?;
+                }
+                Node::Extends { .. } => {}
+                Node::Block { name, body } => match self.blocks.get(name).cloned() {
+                    Some((owner, body)) => self.render_nodes(&owner, &body)?,
+                    None => self.render_nodes(template, body)?,
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn eval(&self, template: &Template, expr: &Expr) -> Result<Value, Error> {
+        Ok(match expr {
+            Expr::Var(_) | Expr::Lookup(..) => self.resolve(template, expr)?.into_owned(),
+            Expr::Literal(value) => value.clone(),
+            Expr::Not(expr) => Value::Bool(!self.eval(template, expr)?.is_truthy()),
+            Expr::And(left, right) => Value::Bool(
+                self.eval(template, left)?.is_truthy() && self.eval(template, right)?.is_truthy(),
+            ),
+            Expr::Or(left, right) => Value::Bool(
+                self.eval(template, left)?.is_truthy() || self.eval(template, right)?.is_truthy(),
+            ),
+            Expr::Compare(left, op, right) => {
+                let (left, right) = (self.eval(template, left)?, self.eval(template, right)?);
+                let ordering = || left.compare(&right);
+                Value::Bool(match op {
+                    CompareOp::Eq => left.loosely_equals(&right),
+                    CompareOp::Ne => !left.loosely_equals(&right),
+                    CompareOp::Lt => ordering().is_some_and(|o| o.is_lt()),
+                    CompareOp::Le => ordering().is_some_and(|o| o.is_le()),
+                    CompareOp::Gt => ordering().is_some_and(|o| o.is_gt()),
+                    CompareOp::Ge => ordering().is_some_and(|o| o.is_ge()),
+                    CompareOp::In => left.is_in(&right),
+                    CompareOp::NotIn => !left.is_in(&right),
+                })
+            }
+            Expr::UrlFor { name, args, line } => {
+                let values = args
+                    .iter()
+                    .map(|(key, expr)| Ok((key.as_str(), self.eval(template, expr)?.to_string())))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let args: Vec<(&str, &str)> =
+                    values.iter().map(|(k, v)| (*k, v.as_str())).collect();
+                let url = self.names.url_for(name, &args).map_err(|e| {
+                    parser::syntax_error(&template.name, *line, format!("url_for: {}", e))
+                })?;
+                Value::Str(url)
+            }
+        })
+    }
+
+    /// Evaluates an expression without copying the variables it refers to.
+    /// Variables are looked up in the loop scopes, then the context; missing
+    /// variables, fields and indices are `Null`.
+    fn resolve(&self, template: &Template, expr: &Expr) -> Result<Cow<'_, Value>, Error> {
+        match expr {
+            Expr::Var(name) => Ok(self
+                .scopes
+                .iter()
+                .rev()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value)
+                .or_else(|| self.context.get(name))
+                .map_or(Cow::Owned(Value::Null), Cow::Borrowed)),
+            Expr::Lookup(base, key) => {
+                let key = self.eval(template, key)?;
+                Ok(match self.resolve(template, base)? {
+                    Cow::Borrowed(value) => value
+                        .get(&key)
+                        .map_or(Cow::Owned(Value::Null), Cow::Borrowed),
+                    Cow::Owned(value) => Cow::Owned(value.get(&key).cloned().unwrap_or_default()),
+                })
+            }
+            _ => self.eval(template, expr).map(Cow::Owned),
+        }
+    }
+
+    fn unknown_filter(&self, name: &str) -> String {
+        let mut message = format!("unknown filter '{}'", name);
+        if self.env.is_default() {
+            message.push_str(
+                " (filters added with App::add_filter are only available while the app \
+                 handles a request; render through App::environment() elsewhere)",
+            );
+        }
+        message
+    }
+
+    fn push_text(&mut self, text: &str) {
+        self.state = text.chars().fold(self.state, HtmlState::next);
+        self.out.push_str(text);
+    }
+
+    /// Writes a value, HTML-escaped for where it appears if `escape` is set.
+    fn push_value(&mut self, value: &str, escape: bool) {
+        match (escape, self.state) {
+            (false, _) => self.out.push_str(value),
+            (true, HtmlState::AttrStart | HtmlState::Unquoted) => {
+                self.out.push_str(&escape_unquoted_attribute(value));
+                self.state = HtmlState::Unquoted;
+            }
+            (true, _) => self.out.push_str(&escape_html(value)),
+        }
+    }
+}
+
+/// Whether values written by the template `file` are HTML-escaped: they are
+/// in `.html` and `.htm` templates, whatever page they are rendered into.
+fn escapes(file: &str) -> bool {
+    file.ends_with(".html") || file.ends_with(".htm")
+}
+
+/// Collects the blocks defined in `nodes` of `template`, at any depth,
+/// keeping overrides that are already present (from templates further down
+/// the chain).
+fn collect_blocks(
+    template: &Arc<Template>,
+    nodes: &[Node],
+    blocks: &mut HashMap<String, (Arc<Template>, Arc<[Node]>)>,
+) {
+    for node in nodes {
+        match node {
+            Node::Block { name, body } => {
+                blocks
+                    .entry(name.clone())
+                    .or_insert_with(|| (template.clone(), body.clone()));
+                collect_blocks(template, body, blocks);
+            }
+            Node::If {
+                branches,
+                otherwise,
+            } => {
+                for (_, body) in branches {
+                    collect_blocks(template, body, blocks);
+                }
+                collect_blocks(template, otherwise, blocks);
+            }
+            Node::For { body, empty, .. } => {
+                collect_blocks(template, body, blocks);
+                collect_blocks(template, empty, blocks);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use super::ser;
+use crate::error::Error;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A value available to templates: a string, number, boolean, list or map.
+///
+/// Values convert from the common Rust types with `From`, so they can be
+/// added to a [`Context`] directly, and from any `Serialize` type with
+/// [`Value::from_serialize`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Value {
+    /// A missing or empty value; renders as nothing.
+    #[default]
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Converts any `Serialize` type: structs and maps become maps,
+    /// sequences become lists, and `None` becomes `Null`. Fails with a `500`
+    /// error for maps whose keys are not strings or numbers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use serde::Serialize;
+    /// use velto::template::Value;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Product {
+    ///     name: String,
+    ///     price: u32,
+    /// }
+    ///
+    /// let product = Product { name: "Lamp".into(), price: 40 };
+    /// let value = Value::from_serialize(&product).unwrap();
+    /// assert_eq!(value.get(&"price".into()), Some(&Value::Int(40)));
+    /// ```
+    pub fn from_serialize<T: Serialize + ?Sized>(value: &T) -> Result<Value, Error> {
+        ser::to_value(value)
+            .map_err(|e| Error::internal(format!("Cannot use value in a template: {}", e)))
+    }
+
+    /// Whether the value counts as true in `{% if %}`: everything except
+    /// `none`, `false`, zero and empty strings, lists and maps.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Int(n) => *n != 0,
+            Value::Float(n) => *n != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::List(items) => !items.is_empty(),
+            Value::Map(map) => !map.is_empty(),
+        }
+    }
+
+    /// Returns a map's field or a list's item, as looked up by `value.field`
+    /// and `value[key]` in templates. Negative indices count from the end.
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        match (self, key) {
+            (Value::Map(map), key) => map.get(key.to_string().as_str()),
+            (Value::List(items), key) => {
+                let index = match key {
+                    Value::Int(n) => *n,
+                    Value::Str(s) => s.trim().parse().ok()?,
+                    _ => return None,
+                };
+                let index = if index < 0 {
+                    items.len().checked_sub(index.unsigned_abs() as usize)?
+                } else {
+                    index as usize
+                };
+                items.get(index)
+            }
+            _ => None,
+        }
+    }
+
+    /// The value as a number, if it is one or a string that parses as one.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(n) => Some(*n as f64),
+            Value::Float(n) => Some(*n),
+            Value::Str(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Compares two values, numerically when both are numbers (or numeric
+    /// strings) and otherwise by their text.
+    pub(crate) fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Str(a), Value::Str(b)) => Some(a.cmp(b)),
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => Some(self.to_string().cmp(&other.to_string())),
+            },
+        }
+    }
+
+    /// Equality as used by `==` in templates, where `3` equals `"3"`.
+    pub(crate) fn loosely_equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::List(_) | Value::Map(_), _) | (_, Value::List(_) | Value::Map(_)) => {
+                self == other
+            }
+            (Value::Null, _) | (_, Value::Null) => self == other,
+            _ => self.compare(other) == Some(Ordering::Equal),
+        }
+    }
+
+    /// Whether `self` is contained in `other`: an item of a list, a key of
+    /// a map or a substring of a string.
+    pub(crate) fn is_in(&self, other: &Value) -> bool {
+        match other {
+            Value::List(items) => items.iter().any(|item| self.loosely_equals(item)),
+            Value::Map(map) => map.contains_key(&self.to_string()),
+            Value::Str(s) => s.contains(&self.to_string()),
+            _ => false,
+        }
+    }
+
+    /// A short name for the value's type, for error messages.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "none",
+            Value::Bool(_) => "a boolean",
+            Value::Int(_) | Value::Float(_) => "a number",
+            Value::Str(_) => "a string",
+            Value::List(_) => "a list",
+            Value::Map(_) => "a map",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// Renders the value as template output; lists are comma-separated.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(n) => write!(f, "{}", n),
+            Value::Str(s) => f.write_str(s),
+            Value::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                Ok(())
+            }
+            Value::Map(map) => {
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s)
+    }
+}
+
+impl From<&String> for Value {
+    fn from(s: &String) -> Self {
+        Value::Str(s.clone())
+    }
+}
+
+impl From<Cow<'_, str>> for Value {
+    fn from(s: Cow<'_, str>) -> Self {
+        Value::Str(s.into_owned())
+    }
+}
+
+impl From<char> for Value {
+    fn from(c: char) -> Self {
+        Value::Str(c.to_string())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+macro_rules! value_from_int {
+    ($($ty:ty)*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(n: $ty) -> Self {
+                    i64::try_from(n).map_or(Value::Float(n as f64), Value::Int)
+                }
+            }
+        )*
+    };
+}
+
+value_from_int! { i8 i16 i32 i64 isize u8 u16 u32 u64 usize }
+
+impl From<f32> for Value {
+    fn from(n: f32) -> Self {
+        Value::Float(n.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Float(n)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Value::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Clone + Into<Value>> From<&[T]> for Value {
+    fn from(items: &[T]) -> Self {
+        Value::List(items.iter().cloned().map(Into::into).collect())
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> From<BTreeMap<K, V>> for Value {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Value::Map(map.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> From<HashMap<K, V>> for Value {
+    fn from(map: HashMap<K, V>) -> Self {
+        Value::Map(map.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+/// The variables a template is rendered with.
+///
+/// `render!` builds one from its arguments, so values there can be anything
+/// that converts into a [`Value`].
+///
+/// # Example
+///
+/// ```
+/// use velto::template::Context;
+///
+/// let mut context = Context::new();
+/// context.insert("title", "Inbox");
+/// context.insert("unread", 3);
+/// context.insert("labels", vec!["work", "home"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Context {
+    values: HashMap<String, Value>,
+}
+
+impl Context {
+    /// Creates an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a variable, replacing any previous value.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.values.insert(key.into(), value.into());
+    }
+
+    /// Returns the value of a variable.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    /// Builds a context from the fields of a `Serialize` struct or map.
+    /// Fails with a `500` error for other types.
+    pub fn from_serialize<T: Serialize + ?Sized>(value: &T) -> Result<Context, Error> {
+        match Value::from_serialize(value)? {
+            Value::Map(map) => Ok(map.into_iter().collect()),
+            other => Err(Error::internal(format!(
+                "Cannot use {} as a template context; expected a struct or map",
+                other.kind()
+            ))),
+        }
+    }
+}
+
+impl From<&HashMap<&str, &str>> for Context {
+    fn from(map: &HashMap<&str, &str>) -> Self {
+        let values = map
+            .iter()
+            .map(|(k, v)| (k.to_string(), Value::from(*v)))
+            .collect();
+        Context { values }
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> Extend<(K, V)> for Context {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl IntoIterator for Context {
+    type Item = (String, Value);
+    type IntoIter = std::collections::hash_map::IntoIter<String, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Context {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = std::collections::hash_map::Iter<'a, String, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.iter()
+    }
+}
+
+impl<K: Into<String>, V: Into<Value>> FromIterator<(K, V)> for Context {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let values = iter
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect();
+        Context { values }
+    }
+}
@@ -1,39 +1,190 @@
 use crate::http_method::Method;
 use crate::{App, Request, Response};
-use std::str::FromStr;
 
 pub struct TestRequest {
     method: Method,
     path: String,
+    headers: Vec<(String, String)>,
     body: Vec<u8>,
 }
 
 impl TestRequest {
+    /// Creates a request for the given method and path.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `method` is not a valid HTTP method token.
     pub fn new(method: &str, path: &str) -> Self {
         Self {
-            method: Method::from_hyper(
-                &http::Method::from_str(method).unwrap_or(http::Method::GET),
-            ),
+            method: method
+                .parse()
+                .unwrap_or_else(|_| panic!("invalid HTTP method: {:?}", method)),
             path: path.to_string(),
+            headers: Vec::new(),
             body: Vec::new(),
         }
     }
 
+    /// Adds a request header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or value is not a valid header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
     pub fn with_body(mut self, body: &str) -> Self {
         self.body = body.as_bytes().to_vec();
         self
     }
 
+    /// Sets the body to a `multipart/form-data` body, with the matching
+    /// content type.
+    pub fn multipart(mut self, body: MultipartBody) -> Self {
+        self.headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case("content-type"));
+        self.headers.push((
+            "Content-Type".to_string(),
+            format!("multipart/form-data; boundary={}", MultipartBody::BOUNDARY),
+        ));
+        self.body = body.finish();
+        self
+    }
+
+    /// Sets the body to `value` serialized as JSON, with an
+    /// `application/json` content type. Requires the `json` feature.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` cannot be serialized.
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Serialize + ?Sized>(mut self, value: &T) -> Self {
+        self.body = serde_json::to_vec(value)
+            .unwrap_or_else(|e| panic!("failed to serialize JSON body: {}", e));
+        self.headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case("content-type"));
+        self.headers
+            .push(("Content-Type".to_string(), "application/json".to_string()));
+        self
+    }
+
+    /// Dispatches the request through the app and returns its response.
+    ///
+    /// Runs the app on a fresh single-threaded tokio runtime, so it can be used
+    /// from plain `#[test]` functions. Inside an async test use [`send_async`](Self::send_async).
     pub fn send(&self, app: &App) -> Response {
-        let http_method =
-            http::Method::from_str(&format!("{:?}", self.method)).unwrap_or(http::Method::GET);
-        let req = Request::fake(&http_method, &self.path, &self.body);
-        let routes = app.get_routes();
-
-        routes
-            .get(&self.path)
-            .and_then(|m| m.get(&self.method))
-            .map(|handler| handler(&req))
-            .unwrap_or_else(|| Response::from_string("404 Not Found").with_status_code(404))
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build test runtime")
+            .block_on(self.send_async(app))
+    }
+
+    /// Dispatches the request through the app from within an async context.
+    pub async fn send_async(&self, app: &App) -> Response {
+        crate::error::install_panic_hook();
+        let method = http::Method::try_from(&self.method).expect("checked in TestRequest::new");
+        let mut req = Request::fake(&method, &self.path, &self.body);
+        for (name, value) in &self.headers {
+            req.headers_mut().append(
+                http::HeaderName::from_bytes(name.as_bytes())
+                    .unwrap_or_else(|_| panic!("invalid header name: {:?}", name)),
+                http::HeaderValue::from_str(value)
+                    .unwrap_or_else(|_| panic!("invalid header value: {:?}", value)),
+            );
+        }
+        let response = app.handle(req).await;
+        match self.method {
+            Method::HEAD => crate::app::without_body(response),
+            _ => response,
+        }
+    }
+}
+
+/// Builder for `multipart/form-data` bodies, sent with
+/// [`TestRequest::multipart`].
+#[derive(Debug, Clone, Default)]
+pub struct MultipartBody {
+    body: Vec<u8>,
+}
+
+impl MultipartBody {
+    const BOUNDARY: &'static str = "velto-test-boundary-5f3c9a1e";
+
+    /// Creates an empty body.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a text field.
+    pub fn text(self, name: &str, value: &str) -> Self {
+        self.part(&format!("name=\"{}\"", name), None, value.as_bytes())
+    }
+
+    /// Adds a file part.
+    pub fn file(self, name: &str, filename: &str, content_type: &str, contents: &[u8]) -> Self {
+        self.part(
+            &format!("name=\"{}\"; filename=\"{}\"", name, filename),
+            Some(content_type),
+            contents,
+        )
+    }
+
+    fn part(mut self, disposition: &str, content_type: Option<&str>, contents: &[u8]) -> Self {
+        let mut head = format!(
+            "--{}\r\nContent-Disposition: form-data; {}\r\n",
+            Self::BOUNDARY,
+            disposition
+        );
+        if let Some(content_type) = content_type {
+            head.push_str(&format!("Content-Type: {}\r\n", content_type));
+        }
+        head.push_str("\r\n");
+        self.body.extend_from_slice(head.as_bytes());
+        self.body.extend_from_slice(contents);
+        self.body.extend_from_slice(b"\r\n");
+        self
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.body
+            .extend_from_slice(format!("--{}--\r\n", Self::BOUNDARY).as_bytes());
+        self.body
+    }
+}
+
+/// Helpers for reading responses returned by [`TestRequest::send`].
+/// Requires the `json` feature.
+///
+/// # Example
+///
+/// ```
+/// use velto::test::{TestRequest, TestResponse};
+/// use velto::{route, App, Json};
+///
+/// let mut app = App::new();
+/// route!(app, "/ping" => |_req| Json(serde_json::json!({ "pong": true })));
+///
+/// let res = TestRequest::new("GET", "/ping").send(&app);
+/// assert_eq!(res.json::<serde_json::Value>()["pong"], true);
+/// ```
+#[cfg(feature = "json")]
+pub trait TestResponse {
+    /// Deserializes the body as JSON.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the body if it is not JSON or does not match `T`.
+    fn json<T: serde::de::DeserializeOwned>(&self) -> T;
+}
+
+#[cfg(feature = "json")]
+impl TestResponse for Response {
+    fn json<T: serde::de::DeserializeOwned>(&self) -> T {
+        let body = self.body();
+        serde_json::from_str(&body)
+            .unwrap_or_else(|e| panic!("response body is not valid JSON ({}): {}", e, body))
     }
 }
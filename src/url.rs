@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+tokio::task_local! {
+    static CURRENT: Arc<RouteNames>;
+}
+
+/// Route names registered on an app, used to build URLs in reverse.
+///
+/// Routes are named with `App::name` or `route!(app, "/path" as "name" => ...)`,
+/// and URLs are generated with `App::url_for`, `Request::url_for` or
+/// `{{ url_for('name', key=value) }}` in templates.
+#[derive(Debug, Clone, Default)]
+pub struct RouteNames {
+    paths: HashMap<String, String>,
+}
+
+impl RouteNames {
+    /// Registers `name` for the route pattern `path`.
+    ///
+    /// Returns an error if the name is already taken.
+    pub(crate) fn insert(&mut self, name: &str, path: &str) -> Result<(), UrlError> {
+        if self.paths.contains_key(name) {
+            return Err(UrlError::DuplicateName(name.to_string()));
+        }
+        self.paths.insert(name.to_string(), path.to_string());
+        Ok(())
+    }
+
+    /// Returns the route pattern registered under `name`.
+    pub fn path(&self, name: &str) -> Option<&str> {
+        self.paths.get(name).map(String::as_str)
+    }
+
+    /// Iterates over all `(name, pattern)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.paths.iter().map(|(n, p)| (n.as_str(), p.as_str()))
+    }
+
+    /// Builds the URL for the named route.
+    ///
+    /// Each `:param` and `*wildcard` in the pattern is filled from `params` and
+    /// percent-encoded. Parameters the pattern does not use are appended as a
+    /// query string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use velto::{route, App, Response};
+    ///
+    /// let mut app = App::new();
+    /// route!(app, "/users/:id/edit" as "user_edit" => |_req| Response::from_string("edit"));
+    ///
+    /// let names = app.route_names();
+    /// assert_eq!(names.url_for("user_edit", &[("id", "42")]).unwrap(), "/users/42/edit");
+    /// assert_eq!(
+    ///     names.url_for("user_edit", &[("id", "a b"), ("tab", "profile")]).unwrap(),
+    ///     "/users/a%20b/edit?tab=profile"
+    /// );
+    /// assert!(names.url_for("user_edit", &[]).is_err());
+    /// ```
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        let pattern = self
+            .path(name)
+            .ok_or_else(|| UrlError::UnknownRoute(name.to_string()))?;
+        let lookup = |key: &str| {
+            params
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| *v)
+                .ok_or_else(|| UrlError::MissingParam {
+                    route: name.to_string(),
+                    param: key.to_string(),
+                })
+        };
+
+        let mut used = Vec::new();
+        let mut url = String::new();
+        for segment in pattern.split('/').filter(|s| !s.is_empty()) {
+            url.push('/');
+            if let Some(key) = segment.strip_prefix(':') {
+                url.push_str(&urlencoding::encode(lookup(key)?));
+                used.push(key);
+            } else if let Some(key) = segment.strip_prefix('*') {
+                let value = lookup(key)?;
+                let parts: Vec<_> = value.split('/').map(urlencoding::encode).collect();
+                url.push_str(&parts.join("/"));
+                used.push(key);
+            } else {
+                url.push_str(segment);
+            }
+        }
+        if url.is_empty() {
+            url.push('/');
+        }
+
+        let extra: Vec<String> = params
+            .iter()
+            .filter(|(k, _)| !used.contains(k))
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+            .collect();
+        if !extra.is_empty() {
+            url.push('?');
+            url.push_str(&extra.join("&"));
+        }
+        Ok(url)
+    }
+}
+
+/// An error building a URL for a named route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlError {
+    /// No route was registered under this name.
+    UnknownRoute(String),
+    /// The route pattern needs a parameter that was not supplied.
+    MissingParam { route: String, param: String },
+    /// Two routes were registered under the same name.
+    DuplicateName(String),
+    /// A name was given to a path that has no registered route.
+    UnknownPath(String),
+    /// A `url_for` call in a template failed.
+    InTemplate {
+        template: String,
+        error: Box<UrlError>,
+    },
+}
+
+impl fmt::Display for UrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UrlError::UnknownRoute(name) => write!(f, "no route named '{}'", name),
+            UrlError::MissingParam { route, param } => {
+                write!(f, "route '{}' requires parameter '{}'", route, param)
+            }
+            UrlError::DuplicateName(name) => {
+                write!(f, "route name '{}' is already registered", name)
+            }
+            UrlError::UnknownPath(path) => write!(f, "no route is registered at '{}'", path),
+            UrlError::InTemplate { template, error } => {
+                write!(f, "in template '{}': {}", template, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+/// Runs `fut` with `names` available to templates rendered inside it.
+pub(crate) async fn with_names<F: Future>(names: Arc<RouteNames>, fut: F) -> F::Output {
+    CURRENT.scope(names, fut).await
+}
+
+/// Returns the route names of the app handling the current request, if any.
+pub(crate) fn current() -> Option<Arc<RouteNames>> {
+    CURRENT.try_with(Arc::clone).ok()
+}
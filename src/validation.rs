@@ -0,0 +1,266 @@
+//! Declarative validation of submitted form fields.
+//!
+//! A [`Validator`] checks fields of a parsed form against a list of rules and
+//! collects one message per invalid field. The resulting
+//! [`ValidationErrors`] can be passed to `render!` to re-display the form
+//! with the messages and the values the user entered.
+//!
+//! # Example
+//!
+//! ```
+//! use velto::form;
+//! use velto::validation::Validator;
+//!
+//! let form = form::parse("username=al&email=al.example.com&password=hunter22&confirm=hunter2");
+//! let errors = Validator::new(&form)
+//!     .required("username")
+//!     .min_length("username", 3)
+//!     .email("email")
+//!     .required("password")
+//!     .matches("confirm", "password")
+//!     .sensitive("password")
+//!     .sensitive("confirm")
+//!     .validate()
+//!     .unwrap_err();
+//!
+//! assert_eq!(errors.get("username"), Some("Must be at least 3 characters"));
+//! assert_eq!(errors.get("email"), Some("Must be a valid email address"));
+//! assert_eq!(errors.get("confirm"), Some("Must match password"));
+//!
+//! let context = errors.context();
+//! assert_eq!(context["username"], "al");
+//! assert_eq!(context["username_error"], "Must be at least 3 characters");
+//! assert!(!context.contains_key("password"));
+//! ```
+
+use crate::error::Error;
+use crate::form::FormData;
+use crate::request::Query;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A source of submitted fields that can be validated.
+pub trait Fields {
+    /// Returns the value of the field `name`.
+    fn field(&self, name: &str) -> Option<&str>;
+
+    /// Returns every field as name/value pairs.
+    fn field_pairs(&self) -> Vec<(&str, &str)>;
+}
+
+impl Fields for HashMap<String, String> {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.get(name).map(String::as_str)
+    }
+
+    fn field_pairs(&self) -> Vec<(&str, &str)> {
+        self.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()
+    }
+}
+
+impl Fields for FormData {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.get(name)
+    }
+
+    fn field_pairs(&self) -> Vec<(&str, &str)> {
+        self.iter().collect()
+    }
+}
+
+impl Fields for Query {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.get(name)
+    }
+
+    fn field_pairs(&self) -> Vec<(&str, &str)> {
+        self.iter().collect()
+    }
+}
+
+/// Checks form fields against rules, keeping the first failure per field.
+///
+/// Rules other than [`required`](Self::required) and
+/// [`matches`](Self::matches) pass for fields that are missing or empty, so optional fields only need to be valid when filled in.
+/// Values are trimmed before they are checked.
+pub struct Validator<'a, F: Fields + ?Sized> {
+    fields: &'a F,
+    errors: Vec<(String, String)>,
+    sensitive: Vec<String>,
+}
+
+impl<'a, F: Fields + ?Sized> Validator<'a, F> {
+    /// Starts validating the given fields.
+    pub fn new(fields: &'a F) -> Self {
+        Self {
+            fields,
+            errors: Vec::new(),
+            sensitive: Vec::new(),
+        }
+    }
+
+    /// Requires the field to be present and not blank.
+    pub fn required(self, field: &str) -> Self {
+        let blank = self.value(field).is_empty();
+        self.check_with(field, !blank, "This field is required")
+    }
+
+    /// Requires the field to be at least `min` characters long.
+    pub fn min_length(self, field: &str, min: usize) -> Self {
+        let valid = self.value(field).chars().count() >= min;
+        self.check(
+            field,
+            valid,
+            &format!("Must be at least {} characters", min),
+        )
+    }
+
+    /// Requires the field to be at most `max` characters long.
+    pub fn max_length(self, field: &str, max: usize) -> Self {
+        let valid = self.value(field).chars().count() <= max;
+        self.check(field, valid, &format!("Must be at most {} characters", max))
+    }
+
+    /// Requires the field to look like an email address.
+    pub fn email(self, field: &str) -> Self {
+        let valid = is_email(self.value(field));
+        self.check(field, valid, "Must be a valid email address")
+    }
+
+    /// Requires the field to equal another field, e.g. a password
+    /// confirmation. Unlike other rules, this fails for an empty field when
+    /// the other one is filled in.
+    pub fn matches(self, field: &str, other: &str) -> Self {
+        let valid = self.value(field) == self.value(other);
+        self.check_with(field, valid, &format!("Must match {}", other))
+    }
+
+    /// Requires the field to satisfy `rule`, failing with `message`.
+    pub fn rule(self, field: &str, rule: impl FnOnce(&str) -> bool, message: &str) -> Self {
+        let value = self.value(field);
+        let valid = value.is_empty() || rule(value);
+        self.check_with(field, valid, message)
+    }
+
+    /// Leaves the field's value out of [`ValidationErrors::context`], so
+    /// passwords are not sent back to the browser.
+    pub fn sensitive(mut self, field: &str) -> Self {
+        self.sensitive.push(field.to_string());
+        self
+    }
+
+    /// Finishes validation, returning the errors if any field is invalid.
+    pub fn validate(self) -> Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        let values = self
+            .fields
+            .field_pairs()
+            .into_iter()
+            .filter(|(name, _)| !self.sensitive.iter().any(|s| s == name))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Err(ValidationErrors {
+            errors: self.errors,
+            values,
+        })
+    }
+
+    fn value(&self, field: &str) -> &'a str {
+        self.fields.field(field).unwrap_or("").trim()
+    }
+
+    /// Records `message` unless the rule passed or the field is empty.
+    fn check(self, field: &str, valid: bool, message: &str) -> Self {
+        let valid = valid || self.value(field).is_empty();
+        self.check_with(field, valid, message)
+    }
+
+    fn check_with(mut self, field: &str, valid: bool, message: &str) -> Self {
+        if !valid && !self.errors.iter().any(|(name, _)| name == field) {
+            self.errors.push((field.to_string(), message.to_string()));
+        }
+        self
+    }
+}
+
+/// The messages for invalid fields, together with the submitted values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationErrors {
+    errors: Vec<(String, String)>,
+    values: Vec<(String, String)>,
+}
+
+impl ValidationErrors {
+    /// Returns the message for the field, if it is invalid.
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.errors
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, message)| message.as_str())
+    }
+
+    /// Iterates over the invalid fields and their messages, in rule order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.errors.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the number of invalid fields.
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Returns true if no field is invalid.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns a template context for re-displaying the form: each submitted
+    /// field's value under its own name (except sensitive fields), and each
+    /// message under `<field>_error`.
+    ///
+    /// Pass it as the last argument of `render!`:
+    /// `render!("signup.html", { "title" => "Sign Up" }, errors.context())`.
+    pub fn context(&self) -> HashMap<String, String> {
+        let errors = self
+            .errors
+            .iter()
+            .map(|(name, message)| (format!("{}_error", name), message.clone()));
+        self.values.iter().cloned().chain(errors).collect()
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (field, message)) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}: {}", field, message)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ValidationErrors> for Error {
+    /// A `422 Unprocessable Entity` error listing the invalid fields.
+    fn from(errors: ValidationErrors) -> Self {
+        Error::new(422, errors.to_string())
+    }
+}
+
+/// A loose check for the shape `local@domain.tld`.
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && !value.chars().any(char::is_whitespace)
+        && domain
+            .split_once('.')
+            .is_some_and(|(name, _)| !name.is_empty())
+        && !domain.ends_with('.')
+        && !domain.contains("..")
+}
@@ -0,0 +1,98 @@
+use velto::middleware::Next;
+use velto::test::TestRequest;
+use velto::{route, App, Request, Response};
+
+async fn user(req: Request) -> Response {
+    tokio::task::yield_now().await;
+    Response::from_string(format!("User {}", req.param("id").unwrap_or("")))
+}
+
+async fn tag(req: Request, next: Next) -> Response {
+    let res = next.run(req).await;
+    let body = format!("[{}]", res.body());
+    Response::from_string(body).with_status_code(res.status_code())
+}
+
+fn shout(req: &Request, next: &dyn Fn(&Request) -> Response) -> Response {
+    let res = next(req);
+    Response::from_string(res.body().to_uppercase())
+}
+
+#[test]
+fn test_async_fn_handler() {
+    let mut app = App::new();
+    route!(app, "/users/:id" => user);
+
+    let res = TestRequest::new("GET", "/users/7").send(&app);
+    assert_eq!(res.status_code(), 200);
+    assert_eq!(res.body(), "User 7");
+}
+
+#[test]
+fn test_async_closure_handler() {
+    let mut app = App::new();
+    route!(app, [GET, POST] "/echo" => |req| async move {
+        Response::from_string(format!("{} {}", req.method(), req.path()))
+    });
+
+    let res = TestRequest::new("POST", "/echo").send(&app);
+    assert_eq!(res.body(), "POST /echo");
+}
+
+#[test]
+fn test_async_and_sync_middleware_wrap_async_handler() {
+    let mut app = App::new();
+    app.use_middleware(tag);
+    app.use_middleware(shout);
+    route!(app, "/users/:id" => user);
+
+    let res = TestRequest::new("GET", "/users/ada").send(&app);
+    assert_eq!(res.body(), "[USER ADA]");
+}
+
+#[tokio::test]
+async fn test_send_async_inside_runtime() {
+    let mut app = App::new();
+    route!(app, "/" => |_req| Response::from_string("ok"));
+
+    let res = TestRequest::new("GET", "/").send_async(&app).await;
+    assert_eq!(res.body(), "ok");
+}
+
+#[test]
+fn test_stacked_sync_middleware_under_load() {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .max_blocking_threads(2)
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut app = App::new();
+    for _ in 0..4 {
+        app.use_middleware(shout);
+    }
+    route!(app, "/users/:id" => |req| async move {
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        user(req).await
+    });
+    let app = std::sync::Arc::new(app);
+
+    rt.block_on(async {
+        let requests = (0..16).map(|i| {
+            let app = app.clone();
+            tokio::spawn(async move {
+                TestRequest::new("GET", &format!("/users/{}", i))
+                    .send_async(&app)
+                    .await
+                    .body()
+            })
+        });
+        let all = futures_util::future::join_all(requests);
+        let bodies = tokio::time::timeout(std::time::Duration::from_secs(10), all)
+            .await
+            .expect("stacked sync middleware deadlocked");
+        for (i, body) in bodies.into_iter().enumerate() {
+            assert_eq!(body.unwrap(), format!("USER {}", i));
+        }
+    });
+}
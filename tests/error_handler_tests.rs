@@ -0,0 +1,92 @@
+use velto::test::TestRequest;
+use velto::{render, route, App, Request, Response};
+
+fn app() -> App {
+    let mut app = App::new();
+    route!(app, "/users" => |_req| Response::from_string("users"));
+    route!(app, "/gone" => |_req| Response::from_string("custom").with_status_code(404));
+    app
+}
+
+#[test]
+fn test_default_error_responses() {
+    let app = app();
+    let res = TestRequest::new("GET", "/missing").send(&app);
+    assert_eq!(res.status_code(), 404);
+    assert_eq!(res.body(), "404 Not Found");
+
+    let res = TestRequest::new("DELETE", "/users").send(&app);
+    assert_eq!(res.status_code(), 405);
+    assert_eq!(res.body(), "405 Method Not Allowed");
+}
+
+#[test]
+fn test_not_found_renders_template() {
+    let mut app = app();
+    app.not_found(|_req: &Request| {
+        render!("index.html", {
+            "title" => "Lost?",
+            "message" => "That page does not exist."
+        })
+    });
+
+    let res = TestRequest::new("GET", "/missing").send(&app);
+    assert_eq!(res.status_code(), 404);
+    assert!(res.body().contains("<h1>Lost?</h1>"));
+    assert_eq!(res.headers().get("content-type").unwrap(), "text/html");
+}
+
+#[test]
+fn test_error_handler_keeps_allow_header() {
+    let mut app = app();
+    app.error_handler(405, |req: Request| async move {
+        Response::from_string(format!("{} not allowed", req.method()))
+    });
+
+    let res = TestRequest::new("DELETE", "/users").send(&app);
+    assert_eq!(res.status_code(), 405);
+    assert_eq!(res.body(), "DELETE not allowed");
+    assert_eq!(res.headers().get("allow").unwrap(), "GET, HEAD, OPTIONS");
+}
+
+#[test]
+fn test_json_error_handler_for_json_clients() {
+    let mut app = app();
+    app.not_found(|_req: &Request| Response::from_string("<h1>Not here</h1>"));
+    app.json_error_handler(404, |_req: &Request| {
+        Response::from_string(r#"{"error":"not found"}"#).with_content_type("application/json")
+    });
+
+    let html = TestRequest::new("GET", "/missing")
+        .with_header("Accept", "text/html,application/xhtml+xml")
+        .send(&app);
+    assert_eq!(html.body(), "<h1>Not here</h1>");
+
+    let json = TestRequest::new("GET", "/missing")
+        .with_header("Accept", "application/json")
+        .send(&app);
+    assert_eq!(json.status_code(), 404);
+    assert_eq!(json.body(), r#"{"error":"not found"}"#);
+
+    let plain = TestRequest::new("GET", "/missing").send(&app);
+    assert_eq!(plain.body(), "<h1>Not here</h1>");
+}
+
+#[test]
+fn test_handler_status_is_kept_unless_ok() {
+    let mut app = app();
+    app.not_found(|_req: &Request| velto::response::redirect("/users"));
+
+    let res = TestRequest::new("GET", "/missing").send(&app);
+    assert_eq!(res.status_code(), 302);
+}
+
+#[test]
+fn test_handler_responses_are_not_replaced() {
+    let mut app = app();
+    app.not_found(|_req: &Request| Response::from_string("branded"));
+
+    let res = TestRequest::new("GET", "/gone").send(&app);
+    assert_eq!(res.status_code(), 404);
+    assert_eq!(res.body(), "custom");
+}
@@ -0,0 +1,134 @@
+use velto::form;
+use velto::test::TestRequest;
+use velto::{route, try_render, App, Error, Request, Response};
+
+fn app() -> App {
+    let mut app = App::new();
+    route!(app, "/users/:id" => |req| -> Result<Response, Error> {
+        let id: u32 = req.param("id").unwrap_or("").parse()?;
+        match id {
+            1 => Ok(Response::from_string("alice")),
+            _ => Err(Error::not_found(format!("no user {}", id))),
+        }
+    });
+    route!(app, "/async/:id" => |req| async move {
+        let id: u32 = req.param("id").unwrap_or("").parse()?;
+        Ok::<_, Error>(Response::from_string(format!("async {}", id)))
+    });
+    route!(app, POST "/signup" => |req| -> Result<Response, Error> {
+        let form = form::try_parse(req.body())?;
+        Ok(Response::from_string(format!("Hi {}", form.get("name").map_or("", |n| n.as_str()))))
+    });
+    route!(app, "/page" => |_req| try_render!("missing.html", {}));
+    app.serve_static("static");
+    app
+}
+
+#[test]
+fn test_result_handlers() {
+    let app = app();
+    assert_eq!(
+        TestRequest::new("GET", "/users/1").send(&app).body(),
+        "alice"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/async/5").send(&app).body(),
+        "async 5"
+    );
+
+    let res = TestRequest::new("GET", "/users/2").send(&app);
+    assert_eq!(res.status_code(), 404);
+    assert_eq!(res.body(), "404 Not Found");
+
+    assert_eq!(
+        TestRequest::new("GET", "/users/abc")
+            .send(&app)
+            .status_code(),
+        500
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/async/abc")
+            .send(&app)
+            .status_code(),
+        500
+    );
+}
+
+#[test]
+fn test_errors_use_status_error_handlers() {
+    let mut app = app();
+    app.not_found(|req: &Request| Response::from_string(format!("Nothing at {}", req.path())));
+
+    let res = TestRequest::new("GET", "/users/2").send(&app);
+    assert_eq!(res.status_code(), 404);
+    assert_eq!(res.body(), "Nothing at /users/2");
+}
+
+#[test]
+fn test_on_error_hook() {
+    let mut app = app();
+    app.on_error(|err, req| {
+        Response::from_string(format!("{} on {}: {}", err.status(), req.path(), err))
+            .with_status_code(err.status())
+    });
+
+    let res = TestRequest::new("GET", "/users/abc").send(&app);
+    assert_eq!(res.status_code(), 500);
+    assert_eq!(
+        res.body(),
+        "500 on /users/abc: invalid digit found in string"
+    );
+
+    let res = TestRequest::new("GET", "/page").send(&app);
+    assert_eq!(res.status_code(), 500);
+    assert_eq!(
+        res.body(),
+        "500 on /page: Template 'missing.html' not found"
+    );
+}
+
+#[test]
+fn test_form_errors_are_bad_requests() {
+    let app = app();
+    assert_eq!(
+        TestRequest::new("POST", "/signup")
+            .with_body("name=Ada")
+            .send(&app)
+            .body(),
+        "Hi Ada"
+    );
+    assert_eq!(
+        TestRequest::new("POST", "/signup")
+            .with_body("name=%E0%A4%A")
+            .send(&app)
+            .status_code(),
+        400
+    );
+}
+
+#[test]
+fn test_static_traversal_is_forbidden() {
+    let mut app = app();
+    app.error_handler(403, |_req: &Request| Response::from_string("No peeking"));
+
+    assert_eq!(
+        TestRequest::new("GET", "/ind.html")
+            .send(&app)
+            .status_code(),
+        200
+    );
+    let res = TestRequest::new("GET", "/../Cargo.toml").send(&app);
+    assert_eq!(res.status_code(), 403);
+    assert_eq!(res.body(), "No peeking");
+}
+
+#[test]
+fn test_error_accessors() {
+    let err: Error = "x".parse::<u8>().unwrap_err().into();
+    assert_eq!(err.status(), 500);
+    assert!(err.source().is_some());
+
+    let err = Error::bad_request("bad").with_status(422);
+    assert_eq!(err.status(), 422);
+    assert_eq!(err.message(), "bad");
+}
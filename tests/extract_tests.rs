@@ -0,0 +1,234 @@
+use serde::Deserialize;
+#[cfg(feature = "json")]
+use velto::extract::Json;
+use velto::extract::{Form, Headers, Path, Query, State};
+use velto::test::TestRequest;
+use velto::{route, App, Error, Request};
+
+const FORM: &str = "application/x-www-form-urlencoded";
+
+#[derive(Deserialize)]
+struct SignupForm {
+    username: String,
+    age: u8,
+    newsletter: Option<bool>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Search {
+    q: String,
+    page: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Color {
+    Red,
+    Blue,
+}
+
+struct Greeting(&'static str);
+
+async fn signup(Form(form): Form<SignupForm>) -> String {
+    format!(
+        "{} {} {:?} {:?}",
+        form.username, form.age, form.newsletter, form.tags
+    )
+}
+
+fn app() -> App {
+    let mut app = App::with_state(Greeting("Hello"));
+    route!(app, POST "/signup" => signup);
+    route!(app, "/users/:id" => |Path(id): Path<u32>| async move { format!("user {}", id) });
+    route!(app, "/repos/:owner/:name" => |Path((owner, name)): Path<(String, String)>| async move {
+        format!("{}/{}", owner, name)
+    });
+    route!(app, "/colors/:color" => |Path(color): Path<Color>| async move { format!("{:?}", color) });
+    route!(app, "/search" => |Query(search): Query<Search>| async move {
+        format!("{} page {}", search.q, search.page.unwrap_or(1))
+    });
+    route!(app, "/greet/:name" => |State(greeting): State<Greeting>, Path(name): Path<String>| async move {
+        format!("{}, {}!", greeting.0, name)
+    });
+    route!(app, "/agent" => |Headers(headers): Headers| async move {
+        headers.get("user-agent").and_then(|v| v.to_str().ok()).unwrap_or("none").to_string()
+    });
+    route!(app, "/missing-state" => |_: State<String>| async move { "unreachable" });
+    route!(app, "/optional" => |search: Option<Query<Search>>, req: Request| async move {
+        format!("{} {}", search.map_or("none".to_string(), |Query(s)| s.q), req.path())
+    });
+    route!(app, "/manual" => |form: Result<Form<SignupForm>, Error>| async move {
+        match form {
+            Ok(_) => "ok".to_string(),
+            Err(e) => format!("handled {}", e.status()),
+        }
+    });
+    app
+}
+
+#[test]
+fn test_form_extractor() {
+    let res = TestRequest::new("POST", "/signup")
+        .with_header("Content-Type", FORM)
+        .with_body("username=ferris&age=8&newsletter=on&tags=a&tags=b")
+        .send(&app());
+    assert_eq!(res.body(), r#"ferris 8 Some(true) ["a", "b"]"#);
+
+    let res = TestRequest::new("POST", "/signup")
+        .with_header(
+            "Content-Type",
+            "application/x-www-form-urlencoded; charset=UTF-8",
+        )
+        .with_body("username=ferris&age=8&newsletter=")
+        .send(&app());
+    assert_eq!(res.body(), "ferris 8 None []");
+}
+
+#[test]
+fn test_form_rejections() {
+    let app = app();
+    let status = |content_type: &str, body: &str| {
+        TestRequest::new("POST", "/signup")
+            .with_header("Content-Type", content_type)
+            .with_body(body)
+            .send(&app)
+            .status_code()
+    };
+    assert_eq!(status("text/plain", "username=ferris&age=8"), 415);
+    assert_eq!(status(FORM, "username=%E0%A4%A&age=8"), 400);
+    assert_eq!(status(FORM, "username=ferris"), 422);
+    assert_eq!(status(FORM, "username=ferris&age=old"), 422);
+
+    let res = TestRequest::new("POST", "/signup")
+        .with_body("username=ferris&age=8")
+        .send(&app);
+    assert_eq!(res.status_code(), 415);
+}
+
+#[test]
+fn test_path_extractor() {
+    let app = app();
+    assert_eq!(
+        TestRequest::new("GET", "/users/42").send(&app).body(),
+        "user 42"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/users/abc")
+            .send(&app)
+            .status_code(),
+        400
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/repos/rust-lang/cargo")
+            .send(&app)
+            .body(),
+        "rust-lang/cargo"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/colors/blue").send(&app).body(),
+        "Blue"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/colors/green")
+            .send(&app)
+            .status_code(),
+        400
+    );
+}
+
+#[test]
+fn test_query_extractor() {
+    let app = app();
+    assert_eq!(
+        TestRequest::new("GET", "/search?q=velto&page=3")
+            .send(&app)
+            .body(),
+        "velto page 3"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/search?q=velto").send(&app).body(),
+        "velto page 1"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/search?page=3")
+            .send(&app)
+            .status_code(),
+        400
+    );
+}
+
+#[test]
+fn test_state_and_headers_extractors() {
+    let app = app();
+    assert_eq!(
+        TestRequest::new("GET", "/greet/Ada").send(&app).body(),
+        "Hello, Ada!"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/agent")
+            .with_header("User-Agent", "test-client")
+            .send(&app)
+            .body(),
+        "test-client"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/missing-state")
+            .send(&app)
+            .status_code(),
+        500
+    );
+}
+
+#[test]
+fn test_optional_and_fallible_extractors() {
+    let app = app();
+    assert_eq!(
+        TestRequest::new("GET", "/optional?q=x").send(&app).body(),
+        "x /optional"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/optional").send(&app).body(),
+        "none /optional"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/manual").send(&app).body(),
+        "handled 415"
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json_extractor() {
+    #[derive(Deserialize)]
+    struct Item {
+        name: String,
+        qty: u32,
+    }
+
+    let mut app = App::new();
+    route!(app, POST "/items" => |Json(item): Json<Item>| async move {
+        format!("{} x{}", item.name, item.qty)
+    });
+    let post = |content_type: &str, body: &str| {
+        TestRequest::new("POST", "/items")
+            .with_header("Content-Type", content_type)
+            .with_body(body)
+            .send(&app)
+    };
+
+    assert_eq!(
+        post("application/json", r#"{"name":"apple","qty":3}"#).body(),
+        "apple x3"
+    );
+    assert_eq!(
+        post("text/plain", r#"{"name":"apple","qty":3}"#).status_code(),
+        415
+    );
+    assert_eq!(post("application/json", r#"{"name":"#).status_code(), 400);
+    assert_eq!(
+        post("application/json", r#"{"name":"apple"}"#).status_code(),
+        422
+    );
+}
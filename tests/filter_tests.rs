@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use velto::template::{render_with_context, Context, Value};
+use velto::test::TestRequest;
+use velto::{route, App, Error, Request, Response};
+
+fn render(file: &str, context: &Context) -> Result<String, Error> {
+    render_with_context(&format!("../tests/templates/filters/{}", file), context)
+}
+
+fn errors_page(mode: &str) -> Result<String, Error> {
+    let mut context = Context::new();
+    context.insert("mode", mode);
+    context.insert("name", "Ada");
+    context.insert("price", 40.5);
+    render("errors.html", &context)
+}
+
+fn currency(value: &Value, args: &[Value]) -> Result<Value, Error> {
+    let amount = value
+        .as_f64()
+        .ok_or_else(|| Error::internal("not a number"))?;
+    let symbol = args.first().map_or("$".to_string(), Value::to_string);
+    Ok(format!("{}{:.2}", symbol, amount).into())
+}
+
+fn errors_route(app: &mut App) {
+    route!(app, "/page/:mode" => |req| {
+        let mode = req.param("mode").unwrap_or("");
+        Response::from_string(errors_page(mode).unwrap_or_else(|e| e.to_string()))
+    });
+}
+
+#[test]
+fn test_builtin_filters() {
+    let mut context = Context::new();
+    context.insert("name", "Ada Lovelace");
+    context.insert("bio", "The first computer programmer");
+    context.insert("price", 1234.5);
+    context.insert("big", 1234567);
+    context.insert("neg", -9876.54);
+    context.insert("created", "2024-03-09T14:05:00Z");
+    context.insert("stamp", 1_700_000_000);
+    context.insert("tags", vec!["rust", "web"]);
+    context.insert("query", "a b&c");
+    context.insert(
+        "params",
+        BTreeMap::from([("q", Value::from("x y")), ("page", Value::from(2))]),
+    );
+    context.insert(
+        "data",
+        BTreeMap::from([("name", Value::from("</script>")), ("n", Value::from(1))]),
+    );
+
+    let html = render("filters.html", &context).unwrap();
+    let lines: Vec<&str> = html.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "ADA LOVELACE|ada...|The first...|The first…",
+            "1,234.5|1,234.50|1,234,567|-9,876.5",
+            "2024-03-09|09 Mar 2024, 14:05|Tuesday 2023-11-14 22:13:20|318 10PM",
+            "anon|2|12|rust, web|RUST / WEB",
+            "a%20b%26c|page=2&amp;q=x%20y",
+            "{&quot;n&quot;:1,&quot;name&quot;:&quot;\\u003c/script\\u003e&quot;}",
+            "<script>const data = {\"n\":1,\"name\":\"\\u003c/script\\u003e\"};</script>",
+        ]
+    );
+}
+
+#[test]
+fn test_format_number_parses_numeric_strings() {
+    let mut context = Context::new();
+    context.insert("plus", "+123");
+    context.insert("exp", "1e5");
+    context.insert("padded", " -1234567.25 ");
+    context.insert("max", i64::MAX);
+    assert_eq!(
+        render("numbers.html", &context).unwrap(),
+        "123|100,000|-1,234,567.25|9,223,372,036,854,775,807\n"
+    );
+
+    context.insert("plus", "inf");
+    let err = render("numbers.html", &context).unwrap_err();
+    assert!(err
+        .to_string()
+        .ends_with("filter 'format_number': expected a number, found a string"));
+}
+
+#[test]
+fn test_filter_errors() {
+    let message = |mode: &str| errors_page(mode).unwrap_err().to_string();
+    let prefix = "Template '../tests/templates/filters/errors.html' line 1: ";
+    assert_eq!(
+        message("count"),
+        format!(
+            "{}filter 'truncate': expected a count, found a string",
+            prefix
+        )
+    );
+    assert_eq!(
+        message("date"),
+        format!("{}filter 'date': 'Ada' is not a date", prefix)
+    );
+    // Outside of a request, application filters are not available, and the
+    // message says how to use them.
+    let hint = " (filters added with App::add_filter are only available while the app \
+                handles a request; render through App::environment() elsewhere)";
+    assert_eq!(
+        message("unknown"),
+        format!("{}unknown filter 'shout'{}", prefix, hint)
+    );
+    assert_eq!(
+        message("custom"),
+        format!("{}unknown filter 'currency'{}", prefix, hint)
+    );
+}
+
+#[test]
+fn test_app_filters() {
+    let mut app = App::new();
+    app.add_filter("currency", currency);
+    errors_route(&mut app);
+
+    let res = TestRequest::new("GET", "/page/custom").send(&app);
+    assert_eq!(res.body(), "$40.50\n");
+    let res = TestRequest::new("GET", "/page/unknown").send(&app);
+    assert!(
+        res.body().ends_with("unknown filter 'shout'"),
+        "{}",
+        res.body()
+    );
+}
+
+#[test]
+fn test_app_filters_outside_requests() {
+    let mut app = App::new();
+    app.add_filter("currency", currency);
+    let env = app.environment();
+    // The environment is shared, so filters added later are seen too.
+    app.add_filter("date", |_: &Value, _: &[Value]| Ok("added later".into()));
+
+    let job = std::thread::spawn(move || {
+        let mut context = Context::new();
+        context.insert("price", 40.5);
+        let custom = env.render("../tests/templates/filters/errors.html", &context);
+        context.insert("mode", "date");
+        let date = env.render("../tests/templates/filters/errors.html", &context);
+        (custom.unwrap(), date.unwrap())
+    });
+    let (custom, date) = job.join().unwrap();
+    assert_eq!(custom, "$40.50\n");
+    assert_eq!(date, "added later\n");
+}
+
+#[test]
+fn test_app_filters_reach_sync_middleware() {
+    let mut app = App::new();
+    app.add_filter("currency", currency);
+    app.use_middleware(|req: &Request, next: &dyn Fn(&Request) -> Response| next(req));
+    errors_route(&mut app);
+
+    let res = TestRequest::new("GET", "/page/custom").send(&app);
+    assert_eq!(res.body(), "$40.50\n");
+}
+
+#[test]
+fn test_app_filters_replace_builtins() {
+    let mut app = App::new();
+    app.add_filter("truncate", |value: &Value, _: &[Value]| Ok(value.clone()));
+    errors_route(&mut app);
+
+    let res = TestRequest::new("GET", "/page/count").send(&app);
+    assert_eq!(res.body(), "Ada\n");
+}
+
+#[test]
+fn test_mounted_app_filters_are_merged() {
+    let mut shop = App::new();
+    shop.add_filter("currency", currency);
+    shop.add_filter("date", |_: &Value, _: &[Value]| Ok("from shop".into()));
+
+    let mut app = App::new();
+    app.add_filter("date", |_: &Value, _: &[Value]| Ok("from app".into()));
+    errors_route(&mut app);
+    app.mount("/shop", shop);
+
+    let res = TestRequest::new("GET", "/page/custom").send(&app);
+    assert_eq!(res.body(), "$40.50\n");
+    let res = TestRequest::new("GET", "/page/date").send(&app);
+    assert_eq!(res.body(), "from app\n");
+}
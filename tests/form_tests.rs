@@ -0,0 +1,211 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use velto::extract::{Form, Query};
+use velto::form::{self, FormData};
+use velto::test::TestRequest;
+use velto::{route, App, Request};
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Order {
+    customer: Customer,
+    items: Vec<Item>,
+    #[serde(default)]
+    gift: bool,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Customer {
+    name: String,
+    address: Address,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Address {
+    city: String,
+    zip: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Item {
+    sku: String,
+    qty: u32,
+}
+
+#[test]
+fn test_parse_keeps_bare_keys_and_decodes_plus() {
+    let form = form::parse("name=Ada+Lovelace&remember&note=1%2B1");
+    assert_eq!(form["name"], "Ada Lovelace");
+    assert_eq!(form["remember"], "");
+    assert_eq!(form["note"], "1+1");
+
+    let form = form::try_parse(b"q=a+b&flag").unwrap();
+    assert_eq!(form["q"], "a b");
+    assert_eq!(form["flag"], "");
+}
+
+#[test]
+fn test_form_data_preserves_order_and_repeats() {
+    let form = FormData::parse("tags=a&name=x&tags=b&&tags=c");
+    assert_eq!(form.get_all("tags"), ["a", "b", "c"]);
+    assert_eq!(form.get("tags"), Some("a"));
+    assert_eq!(form.len(), 4);
+    let keys: Vec<&str> = form.iter().map(|(k, _)| k).collect();
+    assert_eq!(keys, ["tags", "name", "tags", "tags"]);
+    assert!(form.contains_key("name"));
+    assert!(!form.contains_key("missing"));
+
+    assert!(FormData::parse("").is_empty());
+    assert_eq!(FormData::try_parse(b"a=%FF").unwrap_err().status(), 400);
+}
+
+#[test]
+fn test_nested_deserialization() {
+    let form = FormData::parse(
+        "customer[name]=Ada&customer[address][city]=London&customer[address][zip]=\
+         &items[1][sku]=B2&items[1][qty]=1&items[0][sku]=A1&items[0][qty]=3&gift=on",
+    );
+    let order: Order = form.deserialize().unwrap();
+    assert_eq!(
+        order,
+        Order {
+            customer: Customer {
+                name: "Ada".into(),
+                address: Address {
+                    city: "London".into(),
+                    zip: None,
+                },
+            },
+            items: vec![
+                Item {
+                    sku: "A1".into(),
+                    qty: 3
+                },
+                Item {
+                    sku: "B2".into(),
+                    qty: 1
+                },
+            ],
+            gift: true,
+        }
+    );
+}
+
+#[test]
+fn test_bracket_lists_and_maps() {
+    #[derive(Deserialize)]
+    struct Filters {
+        tags: Vec<String>,
+        sizes: Vec<u8>,
+        meta: HashMap<String, String>,
+    }
+
+    let form = FormData::parse("tags[]=a&tags[]=b&sizes=1&sizes=2&meta[k]=v&meta[x]=y");
+    let filters: Filters = form.deserialize().unwrap();
+    assert_eq!(filters.tags, ["a", "b"]);
+    assert_eq!(filters.sizes, [1, 2]);
+    assert_eq!(filters.meta["x"], "y");
+
+    // Malformed brackets are kept as plain keys.
+    let raw: HashMap<String, String> = FormData::parse("a]b=1&c[d=2&e[f]g=3")
+        .deserialize()
+        .unwrap();
+    assert_eq!(raw.len(), 3);
+    assert_eq!(raw["e[f]g"], "3");
+}
+
+#[test]
+fn test_deserialize_errors_are_unprocessable() {
+    let form = FormData::parse("customer[name]=Ada&items[0][sku]=A1&items[0][qty]=many");
+    let err = form.deserialize::<Order>().unwrap_err();
+    assert_eq!(err.status(), 422);
+}
+
+#[test]
+fn test_deeply_nested_keys_are_rejected() {
+    let key = |depth: usize| format!("a{}", "[b]".repeat(depth));
+    let form = FormData::parse(&format!("{}=1", key(2000)));
+    let err = form
+        .deserialize::<HashMap<String, serde::de::IgnoredAny>>()
+        .unwrap_err();
+    assert_eq!(err.status(), 422);
+    assert!(
+        err.to_string().contains("nested more than 32 levels"),
+        "{}",
+        err
+    );
+
+    let form = FormData::parse(&format!("{}=1", key(31)));
+    assert!(form
+        .deserialize::<HashMap<String, serde::de::IgnoredAny>>()
+        .is_ok());
+}
+
+#[test]
+fn test_many_keys_deserialize_in_linear_time() {
+    const KEYS: usize = 100_000;
+    let body: Vec<String> = (0..KEYS)
+        .map(|i| format!("items[{}][sku]=S{}&items[{}][qty]={}", i, i, i, i % 7))
+        .collect();
+    let form = FormData::parse(&body.join("&"));
+
+    #[derive(Deserialize)]
+    struct Cart {
+        items: Vec<Item>,
+    }
+    let started = std::time::Instant::now();
+    let cart: Cart = form.deserialize().unwrap();
+    assert_eq!(cart.items.len(), KEYS);
+    assert_eq!(
+        cart.items[KEYS - 1],
+        Item {
+            sku: format!("S{}", KEYS - 1),
+            qty: ((KEYS - 1) % 7) as u32,
+        }
+    );
+    // Searching the keys seen so far for each new one takes minutes here.
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[test]
+fn test_nested_form_extractor() {
+    let mut app = App::new();
+    route!(app, POST "/orders" => |Form(order): Form<Order>| async move {
+        format!("{} in {}: {} items", order.customer.name, order.customer.address.city, order.items.len())
+    });
+    let res = TestRequest::new("POST", "/orders")
+        .with_header("Content-Type", "application/x-www-form-urlencoded")
+        .with_body("customer%5Bname%5D=Ada&customer[address][city]=New+York&items[0][sku]=A1&items[0][qty]=2")
+        .send(&app);
+    assert_eq!(res.body(), "Ada in New York: 1 items");
+}
+
+#[test]
+fn test_query_keeps_bare_keys() {
+    #[derive(Deserialize)]
+    struct Search {
+        q: String,
+        #[serde(default)]
+        exact: Option<String>,
+    }
+
+    let mut app = App::new();
+    route!(app, "/raw" => |req: &Request| {
+        format!("{:?} {:?}", req.query().get("debug"), req.query().get("q"))
+    });
+    route!(app, "/search" => |Query(search): Query<Search>| async move {
+        format!("{} {}", search.q, search.exact.is_some())
+    });
+
+    assert_eq!(
+        TestRequest::new("GET", "/raw?debug&q=rust+web")
+            .send(&app)
+            .body(),
+        r#"Some("") Some("rust web")"#
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/search?q=a+b&exact")
+            .send(&app)
+            .body(),
+        "a b false"
+    );
+}
@@ -0,0 +1,144 @@
+use velto::test::TestRequest;
+use velto::{route, route_any, App, Error, Request, Response};
+
+fn content_type(res: &Response) -> &str {
+    res.headers().get("content-type").unwrap().to_str().unwrap()
+}
+
+#[test]
+fn test_string_and_bytes_bodies() {
+    let mut app = App::new();
+    route!(app, "/str" => |_req| "static");
+    route!(app, "/string" => |req| format!("path {}", req.path()));
+    route!(app, "/bytes" => |_req| vec![1u8, 2, 3]);
+
+    let res = TestRequest::new("GET", "/str").send(&app);
+    assert_eq!(res.body(), "static");
+    assert_eq!(content_type(&res), "text/plain; charset=utf-8");
+
+    assert_eq!(
+        TestRequest::new("GET", "/string").send(&app).body(),
+        "path /string"
+    );
+
+    let res = TestRequest::new("GET", "/bytes").send(&app);
+    assert_eq!(res.body().as_bytes(), &[1, 2, 3]);
+    assert_eq!(content_type(&res), "application/octet-stream");
+}
+
+#[test]
+fn test_status_and_header_tuples() {
+    let mut app = App::new();
+    route!(app, POST "/items" => |_req| (201, "created"));
+    route!(app, PUT "/items/:id" => |req| {
+        let location = format!("/items/{}", req.param("id").unwrap_or(""));
+        (202, vec![("Location", location), ("X-Queued", "yes".to_string())], String::from("queued"))
+    });
+    route!(app, "/bad-header" => |_req| (200, [("Bad Header", "x")], "oops"));
+
+    let res = TestRequest::new("POST", "/items").send(&app);
+    assert_eq!(res.status_code(), 201);
+    assert_eq!(res.body(), "created");
+
+    let res = TestRequest::new("PUT", "/items/9").send(&app);
+    assert_eq!(res.status_code(), 202);
+    assert_eq!(res.headers().get("location").unwrap(), "/items/9");
+    assert_eq!(res.headers().get("x-queued").unwrap(), "yes");
+    assert_eq!(res.body(), "queued");
+
+    assert_eq!(
+        TestRequest::new("GET", "/bad-header")
+            .send(&app)
+            .status_code(),
+        500
+    );
+}
+
+#[test]
+fn test_repeated_tuple_headers_and_invalid_status() {
+    let mut app = App::new();
+    route!(app, "/vary" => |_req| {
+        (200, [("Vary", "Accept"), ("Content-Type", "text/csv"), ("Vary", "Cookie")], "a,b")
+    });
+    route!(app, "/cookies" => |_req| {
+        (200, [("Set-Cookie", "a=1"), ("Set-Cookie", "b=2")], "")
+    });
+    route!(app, "/status/:code" => |req| {
+        (req.param("code").unwrap().parse::<u16>().unwrap(), "body")
+    });
+    route!(app, "/status-with-headers" => |_req| (1000, [("X-Test", "1")], "body"));
+
+    let res = TestRequest::new("GET", "/vary").send(&app);
+    assert_eq!(res.headers().get("vary").unwrap(), "Accept, Cookie");
+    assert_eq!(content_type(&res), "text/csv");
+
+    let res = TestRequest::new("GET", "/cookies").send(&app);
+    assert_eq!(res.status_code(), 500);
+
+    for code in ["42", "1000"] {
+        let res = TestRequest::new("GET", &format!("/status/{}", code)).send(&app);
+        assert_eq!(res.status_code(), 500, "status {}", code);
+    }
+    assert_eq!(
+        TestRequest::new("GET", "/status/418")
+            .send(&app)
+            .status_code(),
+        418
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/status-with-headers")
+            .send(&app)
+            .status_code(),
+        500
+    );
+}
+
+#[test]
+fn test_option_and_result() {
+    let mut app = App::new();
+    app.not_found(|_req: &Request| "custom 404");
+    route!(app, "/users/:id" => |req| match req.param("id") {
+        Some("1") => Some("alice"),
+        _ => None,
+    });
+    route!(app, "/checked/:n" => |req| async move {
+        let n: i32 = req.param("n").unwrap_or("").parse()?;
+        Ok::<_, Error>((200, format!("{}", n * 2)))
+    });
+
+    assert_eq!(
+        TestRequest::new("GET", "/users/1").send(&app).body(),
+        "alice"
+    );
+    let res = TestRequest::new("GET", "/users/2").send(&app);
+    assert_eq!(res.status_code(), 404);
+    assert_eq!(res.body(), "custom 404");
+
+    assert_eq!(
+        TestRequest::new("GET", "/checked/21").send(&app).body(),
+        "42"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/checked/x")
+            .send(&app)
+            .status_code(),
+        500
+    );
+}
+
+fn named_handler(_req: &Request) -> (u16, &'static str) {
+    (418, "teapot")
+}
+
+#[test]
+fn test_route_and_route_any_accept_into_response() {
+    let mut app = App::new();
+    app.route(velto::http_method::Method::GET, "/tea", named_handler);
+    route_any!(app, "/any" => |req| req.method().to_string());
+
+    assert_eq!(
+        TestRequest::new("GET", "/tea").send(&app).status_code(),
+        418
+    );
+    assert_eq!(TestRequest::new("PATCH", "/any").send(&app).body(), "PATCH");
+}
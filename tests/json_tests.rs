@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use velto::test::{TestRequest, TestResponse};
+use velto::{route, App, Error, Json};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Item {
+    name: String,
+    qty: u32,
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    route!(app, POST "/items" => |Json(item): Json<Item>| async move {
+        (201, Json(Item { qty: item.qty * 2, ..item }))
+    });
+    route!(app, "/items/:id" => |_req| Json(json!({ "id": 1, "tags": ["a", "b"] })));
+    route!(app, "/broken" => |_req| Err::<Json<Value>, _>(Error::internal("database is down")));
+    app
+}
+
+#[test]
+fn test_json_round_trip() {
+    let res = TestRequest::new("POST", "/items")
+        .json(&Item {
+            name: "apple".into(),
+            qty: 3,
+        })
+        .send(&app());
+    assert_eq!(res.status_code(), 201);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    assert_eq!(
+        res.json::<Item>(),
+        Item {
+            name: "apple".into(),
+            qty: 6
+        }
+    );
+
+    let res = TestRequest::new("GET", "/items/1").send(&app());
+    assert_eq!(res.json::<Value>(), json!({ "id": 1, "tags": ["a", "b"] }));
+}
+
+#[test]
+fn test_json_replaces_content_type() {
+    let res = TestRequest::new("POST", "/items")
+        .with_header("Content-Type", "text/plain")
+        .json(&json!({ "name": "pear", "qty": 1 }))
+        .send(&app());
+    assert_eq!(res.status_code(), 201);
+}
+
+#[test]
+fn test_json_error_messages() {
+    let app = app();
+    let post = |body: &str| {
+        TestRequest::new("POST", "/items")
+            .with_header("Content-Type", "application/json")
+            .with_header("Accept", "application/json")
+            .with_body(body)
+            .send(&app)
+    };
+
+    let res = post("");
+    assert_eq!(res.status_code(), 400);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    assert_eq!(
+        res.json::<Value>(),
+        json!({ "status": 400, "error": "Expected a JSON body, found an empty one" })
+    );
+
+    let res = post(r#"{"name": "apple", "qty": }"#);
+    assert_eq!(res.status_code(), 400);
+    let error = res.json::<Value>()["error"].as_str().unwrap().to_string();
+    assert!(error.starts_with("Malformed JSON body"), "{}", error);
+    assert!(error.contains("line 1 column 26"), "{}", error);
+
+    let res = post(r#"{"name": "apple""#);
+    assert_eq!(res.status_code(), 400);
+    assert!(res.json::<Value>()["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Truncated JSON body"));
+
+    let res = post(r#"{"name": "apple", "qty": -1}"#);
+    assert_eq!(res.status_code(), 422);
+    assert_eq!(res.json::<Value>()["status"], 422);
+}
+
+#[test]
+fn test_json_errors_only_for_json_clients() {
+    let app = app();
+    let res = TestRequest::new("POST", "/items")
+        .with_header("Content-Type", "application/json")
+        .with_body("{")
+        .send(&app);
+    assert_eq!(res.status_code(), 400);
+    assert_eq!(res.body(), "400 Bad Request");
+
+    let res = TestRequest::new("GET", "/broken")
+        .with_header("Accept", "application/json")
+        .send(&app);
+    assert_eq!(res.status_code(), 500);
+    assert_eq!(
+        res.json::<Value>(),
+        json!({ "status": 500, "error": "Internal Server Error" })
+    );
+}
+
+#[test]
+#[should_panic(expected = "response body is not valid JSON")]
+fn test_response_json_panics_on_invalid_body() {
+    let mut app = App::new();
+    route!(app, "/text" => |_req| "not json");
+    TestRequest::new("GET", "/text").send(&app).json::<Value>();
+}
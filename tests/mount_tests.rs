@@ -0,0 +1,139 @@
+use velto::middleware::Next;
+use velto::test::TestRequest;
+use velto::{route, App, Request, Response};
+
+async fn tag(req: Request, next: Next) -> Response {
+    let res = next.run(req).await;
+    Response::from_string(format!("billing({})", res.body()))
+}
+
+struct Currency(&'static str);
+
+fn billing_app() -> App {
+    let mut app = App::with_state(Currency("EUR"));
+    app.use_middleware(tag);
+    route!(app, "/" => |_req| Response::from_string("billing home"));
+    route!(app, [GET, DELETE] "/invoices/:id" => |req| {
+        let currency = req.state::<Currency>().map(|c| c.0).unwrap_or("none");
+        Response::from_string(format!("{} {} {}", req.method(), req.param("id").unwrap_or(""), currency))
+    });
+    app.serve_static("static");
+    app
+}
+
+#[test]
+fn test_mount_prefixes_routes() {
+    let mut app = App::new();
+    app.mount("/billing", billing_app());
+
+    assert_eq!(
+        TestRequest::new("GET", "/billing").send(&app).body(),
+        "billing(billing home)"
+    );
+    assert_eq!(
+        TestRequest::new("DELETE", "/billing/invoices/7")
+            .send(&app)
+            .body(),
+        "billing(DELETE 7 EUR)"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/invoices/7")
+            .send(&app)
+            .status_code(),
+        404
+    );
+}
+
+#[test]
+fn test_mounted_middleware_is_scoped() {
+    let mut app = App::new();
+    route!(app, "/" => |_req| Response::from_string("home"));
+    app.mount("/billing", billing_app());
+
+    assert_eq!(TestRequest::new("GET", "/").send(&app).body(), "home");
+}
+
+#[test]
+fn test_mounted_state_overrides_parent() {
+    let mut app = App::with_state(Currency("USD"));
+    route!(app, "/currency" => |req| {
+        Response::from_string(req.state::<Currency>().map(|c| c.0).unwrap_or("none"))
+    });
+    app.mount("/billing", billing_app());
+
+    assert_eq!(
+        TestRequest::new("GET", "/currency").send(&app).body(),
+        "USD"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/billing/invoices/1")
+            .send(&app)
+            .body(),
+        "billing(GET 1 EUR)"
+    );
+}
+
+#[test]
+fn test_mounted_static_dirs_use_prefix() {
+    let mut app = App::new();
+    app.mount("/billing", billing_app());
+
+    let res = TestRequest::new("GET", "/billing/ind.html").send(&app);
+    assert_eq!(res.status_code(), 200);
+    assert_eq!(
+        TestRequest::new("GET", "/ind.html")
+            .send(&app)
+            .status_code(),
+        404
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/billingind.html")
+            .send(&app)
+            .status_code(),
+        404
+    );
+}
+
+#[test]
+fn test_nested_mounts() {
+    let mut billing = billing_app();
+    let mut reports = App::new();
+    route!(reports, "/daily" => |_req| Response::from_string("daily"));
+    billing.mount("/reports", reports);
+
+    let mut app = App::new();
+    app.mount("/billing", billing);
+
+    assert_eq!(
+        TestRequest::new("GET", "/billing/reports/daily")
+            .send(&app)
+            .body(),
+        "billing(daily)"
+    );
+}
+
+#[test]
+fn test_mount_drops_error_handlers_and_template_root() {
+    use velto::template::Environment;
+    use velto::{try_render, Error};
+
+    let mut sub = App::new();
+    sub.set_environment(Environment::with_root("tests/templates/logic"));
+    sub.error_handler(404, |_req: &Request| Response::from_string("sub 404"));
+    route!(sub, "/missing" => |_req: Request| async move {
+        Err::<Response, _>(Error::not_found("no such thing"))
+    });
+    route!(sub, "/footer" => |_req| match try_render!("footer.html", {}) {
+        Ok(_) => Response::from_string("rendered"),
+        Err(err) => Response::from_string(err.to_string()),
+    });
+
+    let mut app = App::new();
+    app.error_handler(404, |_req: &Request| Response::from_string("parent 404"));
+    app.mount("/sub", sub);
+
+    let res = TestRequest::new("GET", "/sub/missing").send(&app);
+    assert_eq!(res.body(), "parent 404");
+    let res = TestRequest::new("GET", "/sub/footer").send(&app);
+    assert_eq!(res.body(), "Template 'footer.html' not found");
+}
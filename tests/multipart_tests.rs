@@ -0,0 +1,260 @@
+use velto::multipart::{Limits, Multipart};
+use velto::test::{MultipartBody, TestRequest};
+use velto::{route, App, Error};
+
+fn upload_app(limits: Option<Limits>) -> App {
+    let mut app = App::new();
+    if let Some(limits) = limits {
+        app.set_multipart_limits(limits);
+    }
+    route!(app, POST "/upload" => |form: Multipart| async move {
+        let mut lines: Vec<String> = form
+            .fields()
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        for file in form.files() {
+            let location = match file.path() {
+                Some(_) => "disk",
+                None => "memory",
+            };
+            let contents = file.read().unwrap();
+            lines.push(format!(
+                "{}:{}:{}:{}:{}",
+                file.name(),
+                file.filename(),
+                file.content_type(),
+                location,
+                String::from_utf8_lossy(&contents)
+            ));
+        }
+        lines.join("\n")
+    });
+    app
+}
+
+#[test]
+fn test_fields_and_files() {
+    let body = MultipartBody::new()
+        .text("title", "Holiday")
+        .text("tag", "beach")
+        .text("tag", "sun")
+        .file("photo", "a.txt", "text/plain", b"first")
+        .file("photo", "b.bin", "application/octet-stream", b"\r\n--x\r\n");
+    let res = TestRequest::new("POST", "/upload")
+        .multipart(body)
+        .send(&upload_app(None));
+    assert_eq!(res.status_code(), 200);
+    assert_eq!(
+        res.body(),
+        "title=Holiday\ntag=beach\ntag=sun\n\
+         photo:a.txt:text/plain:memory:first\n\
+         photo:b.bin:application/octet-stream:memory:\r\n--x\r\n"
+    );
+}
+
+#[test]
+fn test_large_files_go_to_temp_files() {
+    let limits = Limits::new().memory_threshold(4);
+    let body = MultipartBody::new()
+        .file("small", "s.txt", "text/plain", b"tiny")
+        .file("large", "l.txt", "text/plain", b"much larger");
+    let res = TestRequest::new("POST", "/upload")
+        .multipart(body)
+        .send(&upload_app(Some(limits)));
+    assert_eq!(
+        res.body(),
+        "small:s.txt:text/plain:memory:tiny\nlarge:l.txt:text/plain:disk:much larger"
+    );
+}
+
+#[test]
+fn test_temp_file_is_removed_on_drop() {
+    let limits = Limits::new().memory_threshold(0);
+    let body = "--XyZ\r\n\
+        Content-Disposition: form-data; name=\"doc\"; filename=\"notes.txt\"\r\n\
+        \r\n\
+        saved\r\n\
+        --XyZ--\r\n";
+    let form = Multipart::parse(
+        "multipart/form-data; boundary=XyZ",
+        body.as_bytes(),
+        &limits,
+    )
+    .unwrap();
+    let file = &form.files()[0];
+    assert_eq!(file.content_type(), "application/octet-stream");
+    let path = file.path().unwrap().to_path_buf();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "saved");
+    drop(form);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_size_limits() {
+    let limits = Limits::new().max_file_size(8).max_total_size(400);
+    let app = upload_app(Some(limits));
+    let send = |body: MultipartBody| {
+        TestRequest::new("POST", "/upload")
+            .multipart(body)
+            .send(&app)
+            .status_code()
+    };
+
+    assert_eq!(
+        send(MultipartBody::new().file("f", "a", "text/plain", b"12345678")),
+        200
+    );
+    assert_eq!(
+        send(MultipartBody::new().file("f", "a", "text/plain", b"123456789")),
+        413
+    );
+    let mut body = MultipartBody::new();
+    for _ in 0..5 {
+        body = body.text("field", "a value that adds up");
+    }
+    assert_eq!(send(body), 413);
+}
+
+#[test]
+fn test_rejections() {
+    let app = upload_app(None);
+    let status = |content_type: &str, body: &str| {
+        TestRequest::new("POST", "/upload")
+            .with_header("Content-Type", content_type)
+            .with_body(body)
+            .send(&app)
+            .status_code()
+    };
+
+    assert_eq!(status("application/x-www-form-urlencoded", "a=1"), 415);
+    assert_eq!(status("multipart/form-data", "--b\r\n"), 400);
+    assert_eq!(
+        status("multipart/form-data; boundary=b", "no delimiter"),
+        400
+    );
+    assert_eq!(
+        status(
+            "multipart/form-data; boundary=b",
+            "--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nunterminated"
+        ),
+        400
+    );
+    assert_eq!(
+        status(
+            "multipart/form-data; boundary=b",
+            "--b\r\nContent-Type: text/plain\r\n\r\nvalue\r\n--b--"
+        ),
+        400
+    );
+}
+
+#[test]
+fn test_quoted_parameters() {
+    let body = "preamble\r\n--odd\r\n".to_string()
+        + "Content-Disposition: form-data; name=\"note\"; filename=\"say \\\"hi\\\";.txt\"\r\n\
+           Content-Type: text/plain\r\n\
+           \r\n\
+           hello\r\n\
+           --odd--";
+    let form = Multipart::parse(
+        "Multipart/Form-Data; boundary=\"odd\"",
+        body.as_bytes(),
+        &Limits::default(),
+    )
+    .unwrap();
+    let file = form.file("note").unwrap();
+    assert_eq!(file.filename(), "say \"hi\";.txt");
+    assert_eq!(file.bytes().unwrap().as_ref(), b"hello");
+    assert!(form.file("missing").is_none());
+}
+
+#[test]
+fn test_limits_error_hook() {
+    let mut app = upload_app(Some(Limits::new().max_total_size(10)));
+    app.on_error(|err: Error, _req| {
+        velto::Response::from_string(err.to_string()).with_status_code(err.status())
+    });
+    let res = TestRequest::new("POST", "/upload")
+        .multipart(MultipartBody::new().text("a", "b"))
+        .send(&app);
+    assert_eq!(res.status_code(), 413);
+    assert!(res.body().contains("the limit is 10"), "{}", res.body());
+}
+
+#[test]
+fn test_limits_are_enforced_while_reading() {
+    use std::io::Read;
+
+    let content_type = "multipart/form-data; boundary=b";
+    let head = "--b\r\nContent-Disposition: form-data; name=\"f\"; filename=\"big\"\r\n\r\n";
+    // An endless body: parsing has to stop at the limit rather than read it all.
+    let endless = || head.as_bytes().chain(std::io::repeat(b'x'));
+
+    let limits = Limits::new().max_total_size(64 * 1024);
+    let err = Multipart::read(content_type, endless(), &limits).unwrap_err();
+    assert_eq!(err.status(), 413);
+    assert!(err.to_string().starts_with("multipart body"), "{}", err);
+
+    let limits = Limits::new().max_file_size(1000);
+    let err = Multipart::read(content_type, endless(), &limits).unwrap_err();
+    assert_eq!(err.status(), 413);
+    assert!(err.to_string().starts_with("file \"big\""), "{}", err);
+}
+
+#[test]
+fn test_temp_file_names_are_random() {
+    let limits = Limits::new().memory_threshold(0);
+    let body = MultipartBody::new()
+        .file("a", "a.txt", "text/plain", b"one")
+        .file("b", "b.txt", "text/plain", b"two");
+    let body = TestRequest::new("POST", "/")
+        .multipart(body)
+        .send(&{
+            let mut app = App::new();
+            app.set_multipart_limits(limits);
+            route!(app, POST "/" => |form: Multipart| async move {
+                form.files()
+                    .iter()
+                    .map(|file| file.path().unwrap().file_name().unwrap().to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            });
+            app
+        })
+        .body();
+    let names: Vec<&str> = body.split(' ').collect();
+    assert_eq!(names.len(), 2);
+    assert_ne!(names[0], names[1]);
+    for name in names {
+        let suffix = name.strip_prefix("velto-upload-").unwrap();
+        assert_eq!(suffix.len(), 16, "{}", name);
+        assert!(suffix.chars().all(|c| c.is_ascii_hexdigit()), "{}", name);
+    }
+}
+
+#[tokio::test]
+async fn test_optional_multipart_spools_from_async_test() {
+    let mut app = App::new();
+    app.set_multipart_limits(Limits::new().memory_threshold(4));
+    route!(app, POST "/upload" => |form: Option<Multipart>, result: Result<Multipart, Error>| async move {
+        let spooled = |form: &Multipart| form.file("large").and_then(|file| file.path()).is_some();
+        match (form, result) {
+            (Some(form), Ok(again)) => format!("{} {}", spooled(&form), spooled(&again)),
+            _ => "not multipart".to_string(),
+        }
+    });
+
+    let body = MultipartBody::new().file("large", "l.txt", "text/plain", b"much larger");
+    let res = TestRequest::new("POST", "/upload")
+        .multipart(body)
+        .send_async(&app)
+        .await;
+    assert_eq!(res.body(), "true true");
+
+    let res = TestRequest::new("POST", "/upload")
+        .with_body("plain")
+        .send_async(&app)
+        .await;
+    assert_eq!(res.body(), "not multipart");
+}
@@ -0,0 +1,140 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use velto::error::PanicDetails;
+use velto::middleware::Next;
+use velto::test::TestRequest;
+use velto::{route, App, Request, Response};
+
+/// Serializes the tests in this file, since dev mode is global.
+static DEV_MODE: Mutex<()> = Mutex::new(());
+
+fn lock_dev_mode() -> MutexGuard<'static, ()> {
+    DEV_MODE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    route!(app, "/users/:id" => |req| {
+        let id: u32 = req.param("id").unwrap().parse().unwrap();
+        Response::from_string(format!("User {}", id))
+    });
+    route!(app, "/async" => |_req| async move {
+        if true {
+            panic!("async handler failed");
+        }
+        Response::from_string("unreachable")
+    });
+    app
+}
+
+fn sync_passthrough(req: &Request, next: &dyn Fn(&Request) -> Response) -> Response {
+    next(req)
+}
+
+async fn checked(req: Request, next: Next) -> Response {
+    if req.query().contains_key("boom") {
+        panic!("middleware failed");
+    }
+    next.run(req).await
+}
+
+#[test]
+fn test_panics_become_500() {
+    let _dev_mode = lock_dev_mode();
+    let mut app = app();
+    app.use_middleware(checked);
+    app.use_middleware(sync_passthrough);
+
+    let res = TestRequest::new("GET", "/users/abc").send(&app);
+    assert_eq!(res.status_code(), 500);
+    assert!(res.body().starts_with("500 Internal Server Error"));
+
+    assert_eq!(
+        TestRequest::new("GET", "/async").send(&app).status_code(),
+        500
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/users/1?boom=1")
+            .send(&app)
+            .status_code(),
+        500
+    );
+
+    // The app keeps serving after a panic.
+    assert_eq!(
+        TestRequest::new("GET", "/users/1").send(&app).body(),
+        "User 1"
+    );
+}
+
+#[test]
+fn test_custom_500_handler() {
+    let _dev_mode = lock_dev_mode();
+    let mut app = app();
+    app.error_handler(500, |_req: &Request| {
+        Response::from_string("Something broke")
+    });
+
+    let res = TestRequest::new("GET", "/async").send(&app);
+    assert_eq!(res.status_code(), 500);
+    assert_eq!(res.body(), "Something broke");
+}
+
+#[test]
+fn test_panic_details_only_in_dev_mode() {
+    let _dev_mode = lock_dev_mode();
+    let mut app = app();
+    app.error_handler(500, |req: &Request| {
+        let shown = req
+            .state::<PanicDetails>()
+            .map_or("hidden".to_string(), |d| d.message.clone());
+        Response::from_string(shown)
+    });
+    let mut plain = App::new();
+    route!(plain, "/" => |_req| -> Response { panic!("plain failure") });
+
+    velto::set_dev_mode(false);
+    assert_eq!(
+        TestRequest::new("GET", "/users/abc").send(&app).body(),
+        "hidden"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/").send(&plain).body(),
+        "500 Internal Server Error"
+    );
+
+    velto::set_dev_mode(true);
+    assert_eq!(
+        TestRequest::new("GET", "/async").send(&app).body(),
+        "async handler failed"
+    );
+    let body = TestRequest::new("GET", "/").send(&plain).body();
+    velto::set_dev_mode(false);
+
+    assert!(body.contains("plain failure"), "{}", body);
+    assert!(body.contains("tests/panic_tests.rs"), "{}", body);
+}
+
+#[test]
+fn test_unrelated_panics_do_not_change_reported_location() {
+    let _dev_mode = lock_dev_mode();
+    let mut app = App::new();
+    route!(app, "/" => |_req| Response::from_string("ok"));
+    TestRequest::new("GET", "/").send(&app);
+
+    // A panic outside the dispatcher is not recorded, so it cannot show up
+    // as the details of a later handler panic.
+    let _ = std::thread::spawn(|| panic!("background failure")).join();
+    let result = std::panic::catch_unwind(|| panic!("caught by the caller"));
+    assert!(result.is_err());
+
+    velto::set_dev_mode(true);
+    let mut app = App::new();
+    app.error_handler(500, |req: &Request| {
+        let details = req.state::<PanicDetails>().unwrap();
+        Response::from_string(details.location.clone().unwrap_or_default())
+    });
+    route!(app, "/boom" => |_req| -> Response { panic!("handler failure") });
+    let body = TestRequest::new("GET", "/boom").send(&app).body();
+    velto::set_dev_mode(false);
+    assert!(body.starts_with("tests/panic_tests.rs:"), "{}", body);
+}
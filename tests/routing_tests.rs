@@ -0,0 +1,216 @@
+use velto::test::TestRequest;
+use velto::{route, App, Response};
+
+#[test]
+fn test_named_param() {
+    let mut app = App::new();
+    route!(app, "/users/:id" => |req| {
+        Response::from_string(format!("User {}", req.param("id").unwrap_or("")))
+    });
+
+    let res = TestRequest::new("GET", "/users/42").send(&app);
+    assert_eq!(res.status_code(), 200);
+    assert_eq!(res.body(), "User 42");
+
+    let res = TestRequest::new("GET", "/users").send(&app);
+    assert_eq!(res.status_code(), 404);
+}
+
+#[test]
+fn test_wildcard_param() {
+    let mut app = App::new();
+    route!(app, "/files/*rest" => |req| {
+        Response::from_string(req.param("rest").unwrap_or("").to_string())
+    });
+
+    let res = TestRequest::new("GET", "/files/css/site%20main.css").send(&app);
+    assert_eq!(res.body(), "css/site main.css");
+
+    let res = TestRequest::new("GET", "/files/").send(&app);
+    assert_eq!(res.status_code(), 404);
+}
+
+#[test]
+fn test_static_route_beats_param() {
+    let mut app = App::new();
+    route!(app, "/users/:id" => |_req| Response::from_string("param"));
+    route!(app, "/users/me" => |_req| Response::from_string("static"));
+
+    assert_eq!(
+        TestRequest::new("GET", "/users/me").send(&app).body(),
+        "static"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/users/7").send(&app).body(),
+        "param"
+    );
+}
+
+#[test]
+fn test_param_backtracks_when_static_branch_dead_ends() {
+    let mut app = App::new();
+    route!(app, "/users/me" => |_req| Response::from_string("me"));
+    route!(app, "/users/:id/edit" => |req| {
+        Response::from_string(format!("edit {}", req.param("id").unwrap_or("")))
+    });
+
+    let res = TestRequest::new("GET", "/users/me/edit").send(&app);
+    assert_eq!(res.body(), "edit me");
+}
+
+#[test]
+#[should_panic(expected = "conflicts with existing route '/users/:id'")]
+fn test_conflicting_param_names_panic() {
+    let mut app = App::new();
+    route!(app, "/users/:id" => |_req| Response::from_string("id"));
+    route!(app, "/users/:name" => |_req| Response::from_string("name"));
+}
+
+#[test]
+#[should_panic(expected = "already registered")]
+fn test_duplicate_route_panics() {
+    let mut app = App::new();
+    route!(app, "/about" => |_req| Response::from_string("one"));
+    route!(app, "/about" => |_req| Response::from_string("two"));
+}
+
+#[test]
+fn test_router_rejects_invalid_paths() {
+    use velto::router::{RouteError, Router};
+
+    let mut router = Router::new();
+    assert_eq!(
+        router.insert("/files/*rest/more", ()),
+        Err(RouteError::Invalid("/files/*rest/more".to_string()))
+    );
+    assert_eq!(
+        router.insert("/users/:", ()),
+        Err(RouteError::Invalid("/users/:".to_string()))
+    );
+}
+
+#[test]
+fn test_query_string_is_ignored_for_matching() {
+    let mut app = App::new();
+    route!(app, "/search" => |req| {
+        let tags = req.query().get_all("tag").join(",");
+        Response::from_string(format!("{} [{}]", req.query().get("q").unwrap_or(""), tags))
+    });
+
+    let res = TestRequest::new("GET", "/search?q=rust%20web&tag=a&tag=b").send(&app);
+    assert_eq!(res.status_code(), 200);
+    assert_eq!(res.body(), "rust web [a,b]");
+}
+
+#[test]
+fn test_static_file_with_cache_busting_query() {
+    let mut app = App::new();
+    app.serve_static("static");
+
+    let res = TestRequest::new("GET", "/ind.html?v=3").send(&app);
+    assert_eq!(res.status_code(), 200);
+    assert!(res.body().contains("Lorem ipsum"));
+}
+
+#[test]
+fn test_wrong_method_is_405_with_allow_header() {
+    let mut app = App::new();
+    route!(app, [GET, POST] "/items" => |_req| Response::from_string("items"));
+
+    let res = TestRequest::new("DELETE", "/items").send(&app);
+    assert_eq!(res.status_code(), 405);
+    assert_eq!(
+        res.headers().get("allow").unwrap(),
+        "GET, HEAD, POST, OPTIONS"
+    );
+}
+
+#[test]
+fn test_head_uses_get_handler_without_body() {
+    let mut app = App::new();
+    route!(app, "/page" => |_req| {
+        Response::from_string("full body").with_content_type("text/plain")
+    });
+
+    let res = TestRequest::new("HEAD", "/page").send(&app);
+    assert_eq!(res.status_code(), 200);
+    assert_eq!(res.body(), "");
+    assert_eq!(res.headers().get("content-type").unwrap(), "text/plain");
+    assert_eq!(res.headers().get("content-length").unwrap(), "9");
+}
+
+#[test]
+fn test_automatic_and_explicit_options() {
+    let mut app = App::new();
+    route!(app, "/auto" => |_req| Response::from_string("auto"));
+    route!(app, OPTIONS "/custom" => |_req| Response::from_string("custom"));
+
+    let res = TestRequest::new("OPTIONS", "/auto").send(&app);
+    assert_eq!(res.status_code(), 204);
+    assert_eq!(res.headers().get("allow").unwrap(), "GET, HEAD, OPTIONS");
+
+    let res = TestRequest::new("OPTIONS", "/custom").send(&app);
+    assert_eq!(res.body(), "custom");
+}
+
+#[test]
+fn test_route_any_includes_head() {
+    let mut app = App::new();
+    velto::route_any!(app, "/any" => |req| Response::from_string(req.method().to_string()));
+
+    let res = TestRequest::new("HEAD", "/any").send(&app);
+    assert_eq!(res.status_code(), 200);
+    assert_eq!(app.get_routes().at("/any").unwrap().value.len(), 7);
+}
+
+#[test]
+fn test_extension_methods_do_not_fall_back_to_get() {
+    use velto::http_method::Method;
+
+    let mut app = App::new();
+    route!(app, "/cache" => |_req| Response::from_string("cached page"));
+    app.route(
+        Method::Extension("PURGE".to_string()),
+        "/cache",
+        |_req: &velto::Request| Response::from_string("purged"),
+    );
+
+    assert_eq!(
+        TestRequest::new("PURGE", "/cache").send(&app).body(),
+        "purged"
+    );
+
+    let res = TestRequest::new("PROPFIND", "/cache").send(&app);
+    assert_eq!(res.status_code(), 405);
+    assert_eq!(
+        res.headers().get("allow").unwrap(),
+        "GET, HEAD, OPTIONS, PURGE"
+    );
+}
+
+#[test]
+fn test_extension_methods_named_like_standard_ones() {
+    use std::collections::HashSet;
+    use velto::http_method::Method;
+
+    assert_eq!(Method::Extension("GET".to_string()), Method::GET);
+    let methods = HashSet::from([Method::GET, Method::Extension("GET".to_string())]);
+    assert_eq!(methods.len(), 1);
+
+    let mut app = App::new();
+    app.route(
+        Method::Extension("POST".to_string()),
+        "/form",
+        |_req: &velto::Request| Response::from_string("posted"),
+    );
+    assert_eq!(
+        TestRequest::new("POST", "/form").send(&app).body(),
+        "posted"
+    );
+
+    assert!(http::Method::try_from(Method::Extension("bad method".to_string())).is_err());
+    assert_eq!(
+        http::Method::try_from(Method::Extension("PURGE".to_string())).unwrap(),
+        "PURGE"
+    );
+}
@@ -0,0 +1,85 @@
+use velto::middleware::Next;
+use velto::test::TestRequest;
+use velto::{route, App, Request, Response};
+
+async fn outer(req: Request, next: Next) -> Response {
+    let res = next.run(req).await;
+    Response::from_string(format!("outer({})", res.body()))
+}
+
+async fn inner(req: Request, next: Next) -> Response {
+    let res = next.run(req).await;
+    Response::from_string(format!("inner({})", res.body()))
+}
+
+#[test]
+fn test_scope_prefixes_routes() {
+    let mut app = App::new();
+    app.scope("/api/v1/", |api| {
+        route!(api, "/" => |_req| Response::from_string("index"));
+        route!(api, [GET, POST] "/users/:id" => |req| {
+            Response::from_string(format!("{} {}", req.method(), req.param("id").unwrap_or("")))
+        });
+    });
+
+    assert_eq!(
+        TestRequest::new("GET", "/api/v1").send(&app).body(),
+        "index"
+    );
+    assert_eq!(
+        TestRequest::new("POST", "/api/v1/users/9")
+            .send(&app)
+            .body(),
+        "POST 9"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/users/9").send(&app).status_code(),
+        404
+    );
+}
+
+#[test]
+fn test_scope_middleware_is_scoped_and_nests() {
+    let mut app = App::new();
+    app.scope("/admin", |admin| {
+        route!(admin, "/dashboard" => |_req| Response::from_string("dash"));
+        admin.use_middleware(outer);
+        admin.scope("/reports", |reports| {
+            reports.use_middleware(inner);
+            route!(reports, "/daily" => |_req| Response::from_string("daily"));
+        });
+    });
+    route!(app, "/public" => |_req| Response::from_string("public"));
+
+    assert_eq!(
+        TestRequest::new("GET", "/admin/dashboard")
+            .send(&app)
+            .body(),
+        "outer(dash)"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/admin/reports/daily")
+            .send(&app)
+            .body(),
+        "outer(inner(daily))"
+    );
+    assert_eq!(
+        TestRequest::new("GET", "/public").send(&app).body(),
+        "public"
+    );
+}
+
+#[test]
+fn test_global_middleware_wraps_scope_middleware() {
+    let mut app = App::new();
+    app.use_middleware(outer);
+    app.scope("/api", |api| {
+        api.use_middleware(inner);
+        route!(api, "/ping" => |_req| Response::from_string("pong"));
+    });
+
+    assert_eq!(
+        TestRequest::new("GET", "/api/ping").send(&app).body(),
+        "outer(inner(pong))"
+    );
+}
@@ -0,0 +1,98 @@
+//! Tests that start a real server with `App::run` and talk to it over TCP.
+
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use velto::{route, App, Response};
+
+async fn get(addr: &str, path: &str) -> String {
+    send(addr, "GET", path).await
+}
+
+async fn send(addr: &str, method: &str, path: &str) -> String {
+    let mut stream = loop {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => break stream,
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    };
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        method, path, addr
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_slow_handler_does_not_block_other_requests() {
+    const ADDR: &str = "127.0.0.1:38517";
+
+    let mut app = App::new();
+    route!(app, "/slow" => |_req| async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        Response::from_string("slow")
+    });
+    route!(app, "/fast" => |_req| Response::from_string("fast"));
+    tokio::spawn(app.run(ADDR));
+
+    // Make sure the server is up before timing anything.
+    assert!(get(ADDR, "/fast").await.ends_with("fast"));
+
+    let slow = tokio::spawn(get(ADDR, "/slow"));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let started = Instant::now();
+    let fast = get(ADDR, "/fast").await;
+    assert!(fast.ends_with("fast"));
+    assert!(started.elapsed() < Duration::from_millis(300));
+
+    assert!(slow.await.unwrap().ends_with("slow"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_head_sends_get_headers_without_body() {
+    const ADDR: &str = "127.0.0.1:38519";
+
+    let mut app = App::new();
+    route!(app, "/page" => |_req| {
+        Response::from_string("full body").with_content_type("text/plain")
+    });
+    tokio::spawn(app.run(ADDR));
+
+    let response = send(ADDR, "HEAD", "/page").await.to_lowercase();
+    assert!(response.starts_with("http/1.1 200 ok\r\n"));
+    assert!(response.contains("\r\ncontent-type: text/plain\r\n"));
+    assert!(response.contains("\r\ncontent-length: 9\r\n"));
+    assert!(response.ends_with("\r\n\r\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_requests_beyond_max_in_flight_wait() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const ADDR: &str = "127.0.0.1:38521";
+    static RUNNING: AtomicUsize = AtomicUsize::new(0);
+    static MOST: AtomicUsize = AtomicUsize::new(0);
+
+    let mut app = App::new();
+    app.set_max_in_flight(2);
+    route!(app, "/slow" => |_req| async move {
+        MOST.fetch_max(RUNNING.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+        Response::from_string("slow")
+    });
+    tokio::spawn(app.run(ADDR));
+
+    let started = Instant::now();
+    let requests: Vec<_> = (0..6).map(|_| tokio::spawn(get(ADDR, "/slow"))).collect();
+    for request in requests {
+        assert!(request.await.unwrap().ends_with("slow"));
+    }
+    assert_eq!(MOST.load(Ordering::SeqCst), 2);
+    // Six requests, two at a time, take three rounds.
+    assert!(started.elapsed() >= Duration::from_millis(600));
+}
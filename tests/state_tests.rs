@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use velto::middleware::Next;
+use velto::test::TestRequest;
+use velto::{route, App, Request, Response};
+
+trait UserStore: Send + Sync {
+    fn name(&self, id: &str) -> Option<String>;
+}
+
+struct FakeStore;
+
+impl UserStore for FakeStore {
+    fn name(&self, id: &str) -> Option<String> {
+        (id == "1").then(|| "Ada".to_string())
+    }
+}
+
+struct Hits(AtomicUsize);
+
+async fn count_hits(req: Request, next: Next) -> Response {
+    if let Some(hits) = req.state::<Hits>() {
+        hits.0.fetch_add(1, Ordering::SeqCst);
+    }
+    next.run(req).await
+}
+
+async fn user(req: Request) -> Response {
+    let store = req.state::<Box<dyn UserStore>>().unwrap();
+    match store.name(req.param("id").unwrap_or("")) {
+        Some(name) => Response::from_string(name),
+        None => Response::from_string("unknown").with_status_code(404),
+    }
+}
+
+#[test]
+fn test_handlers_receive_injected_state() {
+    let mut app = App::with_state::<Box<dyn UserStore>>(Box::new(FakeStore));
+    route!(app, "/users/:id" => user);
+
+    assert_eq!(TestRequest::new("GET", "/users/1").send(&app).body(), "Ada");
+    assert_eq!(
+        TestRequest::new("GET", "/users/2").send(&app).status_code(),
+        404
+    );
+}
+
+#[test]
+fn test_middleware_receives_state() {
+    let mut app = App::new();
+    app.manage(Hits(AtomicUsize::new(0)));
+    app.use_middleware(count_hits);
+    route!(app, "/" => |req| {
+        let hits = req.state::<Hits>().unwrap().0.load(Ordering::SeqCst);
+        Response::from_string(hits.to_string())
+    });
+
+    TestRequest::new("GET", "/").send(&app);
+    assert_eq!(TestRequest::new("GET", "/").send(&app).body(), "2");
+}
+
+#[test]
+fn test_missing_state_is_none() {
+    let mut app = App::new();
+    route!(app, "/" => |req| {
+        Response::from_string(req.state::<Hits>().is_none().to_string())
+    });
+
+    assert_eq!(TestRequest::new("GET", "/").send(&app).body(), "true");
+}
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::PathBuf;
+use velto::template::{clear_cache, render_with_context, Context, Environment};
+use velto::test::TestRequest;
+use velto::{render, route, App};
+
+/// A scratch template file, named relative to `templates/` for rendering.
+struct Scratch {
+    path: PathBuf,
+    name: String,
+}
+
+impl Scratch {
+    fn new(file: &str) -> Self {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+        let relative = dir
+            .strip_prefix(env!("CARGO_MANIFEST_DIR"))
+            .expect("the target directory is inside the crate");
+        let path = dir.join(file);
+        let _ = fs::remove_file(&path);
+        Scratch {
+            name: format!("../{}/{}", relative.display(), file),
+            path,
+        }
+    }
+
+    fn write(&self, contents: &str) {
+        fs::write(&self.path, contents).unwrap();
+    }
+
+    fn render(&self) -> Result<String, velto::Error> {
+        let mut context = Context::new();
+        context.insert("name", "Ada");
+        render_with_context(&self.name, &context)
+    }
+}
+
+#[test]
+fn test_templates_are_cached_until_cleared() {
+    let scratch = Scratch::new("cached.txt");
+    scratch.write("Hello {{ name }}");
+    assert_eq!(scratch.render().unwrap(), "Hello Ada");
+
+    scratch.write("Goodbye {{ name }}");
+    assert_eq!(scratch.render().unwrap(), "Hello Ada");
+
+    clear_cache();
+    assert_eq!(scratch.render().unwrap(), "Goodbye Ada");
+}
+
+#[test]
+fn test_failures_are_not_cached() {
+    let scratch = Scratch::new("failing.txt");
+    let err = scratch.render().unwrap_err();
+    assert!(err.to_string().ends_with("not found"));
+
+    scratch.write("{% if name %}Hi");
+    let err = scratch.render().unwrap_err();
+    assert!(err.to_string().ends_with("missing {% endif %}"));
+
+    scratch.write("{% if name %}Hi {{ name }}{% endif %}");
+    assert_eq!(scratch.render().unwrap(), "Hi Ada");
+}
+
+/// A scratch directory for templates, emptied first.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn render_in(env: &Environment, file: &str) -> String {
+    let mut context = Context::new();
+    context.insert("name", "Ada");
+    env.render(file, &context).unwrap()
+}
+
+#[test]
+fn test_environments_have_their_own_roots_and_caches() {
+    let (shop, blog) = (scratch_dir("env_shop"), scratch_dir("env_blog"));
+    fs::write(shop.join("page.txt"), "Shop for {{ name }}").unwrap();
+    fs::write(blog.join("page.txt"), "Blog by {{ name }}").unwrap();
+    let shop_env = Environment::with_root(&shop);
+    let blog_env = Environment::with_root(&blog);
+    assert_eq!(render_in(&shop_env, "page.txt"), "Shop for Ada");
+    assert_eq!(render_in(&blog_env, "page.txt"), "Blog by Ada");
+
+    fs::write(shop.join("page.txt"), "Shop closed").unwrap();
+    fs::write(blog.join("page.txt"), "Blog moved").unwrap();
+    shop_env.clear_cache();
+    assert_eq!(render_in(&shop_env, "page.txt"), "Shop closed");
+    assert_eq!(render_in(&blog_env, "page.txt"), "Blog by Ada");
+}
+
+#[test]
+fn test_invalidate_discards_only_changed_templates() {
+    let dir = scratch_dir("env_invalidate");
+    fs::create_dir(dir.join("partials")).unwrap();
+    let env = Environment::with_root(&dir);
+    for file in ["one.txt", "two.txt", "partials/three.txt"] {
+        fs::write(dir.join(file), format!("{} v1", file)).unwrap();
+        assert_eq!(render_in(&env, file), format!("{} v1", file));
+        fs::write(dir.join(file), format!("{} v2", file)).unwrap();
+    }
+
+    env.invalidate(dir.join("one.txt"));
+    assert_eq!(render_in(&env, "one.txt"), "one.txt v2");
+    assert_eq!(render_in(&env, "two.txt"), "two.txt v1");
+    assert_eq!(
+        render_in(&env, "partials/three.txt"),
+        "partials/three.txt v1"
+    );
+
+    // Invalidating a directory discards the templates under it, and a
+    // deleted file can still be invalidated.
+    env.invalidate(dir.join("partials"));
+    assert_eq!(
+        render_in(&env, "partials/three.txt"),
+        "partials/three.txt v2"
+    );
+    fs::remove_file(dir.join("two.txt")).unwrap();
+    env.invalidate(dir.join("two.txt"));
+    assert!(env.render("two.txt", &Context::new()).is_err());
+}
+
+#[test]
+fn test_app_renders_from_its_environment() {
+    let dir = scratch_dir("env_app");
+    fs::write(dir.join("hello.txt"), "Hello from the app, {{ name }}").unwrap();
+    let mut app = App::new();
+    app.set_environment(Environment::with_root(&dir));
+    route!(app, "/" => |_req| render!("hello.txt", { "name" => "Ada" }));
+
+    let res = TestRequest::new("GET", "/").send(&app);
+    assert_eq!(res.body(), "Hello from the app, Ada");
+    // Outside the app, templates still load from `templates/`.
+    assert!(render_with_context("hello.txt", &Context::new()).is_err());
+}
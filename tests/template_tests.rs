@@ -0,0 +1,339 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use velto::template::Value;
+use velto::template::{escape_html, render_with_context, try_render_template, Context};
+use velto::{render, render_template, try_render};
+
+const XSS: &str = r#"<script>alert("x")</script>"#;
+
+fn render(file: &str, name: &str) -> String {
+    let context = HashMap::from([("name", name), ("markup", "<em>trusted</em>")]);
+    render_template(&format!("../tests/templates/escape/{}", file), &context)
+}
+
+#[test]
+fn test_script_in_context_is_escaped() {
+    let html = render("page.html", XSS);
+    assert!(!html.contains("<script>"), "{}", html);
+    assert!(html.contains("<p>&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt;</p>"));
+}
+
+#[test]
+fn test_attribute_values_are_escaped() {
+    let html = render("page.html", r#"" onmouseover='alert(1)' x="#);
+    assert!(html.contains(
+        r#"<input value="&quot; onmouseover=&#x27;alert(1)&#x27; x=" title='&quot; onmouseover=&#x27;alert(1)&#x27; x='>"#
+    ), "{}", html);
+
+    let html = render("page.html", "x onclick=alert(1)");
+    assert!(
+        html.contains("<a href=x&#x20;onclick&#x3D;alert&#x28;1&#x29; class=x>link</a>"),
+        "{}",
+        html
+    );
+}
+
+#[test]
+fn test_safe_and_triple_braces_opt_out() {
+    let html = render("page.html", "Ada");
+    assert!(html.contains("<div><em>trusted</em></div>\n<div><em>trusted</em></div>"));
+    assert!(html.contains(r#"<input value="Ada" title='Ada'>"#));
+}
+
+#[test]
+fn test_non_html_templates_are_not_escaped() {
+    assert_eq!(render("note.txt", "<b>Ada</b>"), "Hello <b>Ada</b>\n");
+}
+
+#[test]
+fn test_escaping_follows_each_included_template() {
+    assert_eq!(
+        render("mixed.html", "<i>Ada</i>"),
+        "<p>&lt;i&gt;Ada&lt;/i&gt;</p>\nHello <i>Ada</i>\n\n"
+    );
+    assert_eq!(
+        render("mixed.txt", "<i>Ada</i>"),
+        "Plain <i>Ada</i>\n<b>&lt;i&gt;Ada&lt;/i&gt;</b>\n\n"
+    );
+}
+
+#[test]
+fn test_escape_html() {
+    assert_eq!(
+        escape_html("a < b && c > d"),
+        "a &lt; b &amp;&amp; c &gt; d"
+    );
+    assert_eq!(escape_html("plain"), "plain");
+}
+
+fn render_logic(file: &str, context: &HashMap<&str, &str>) -> Result<String, velto::Error> {
+    try_render_template(&format!("../tests/templates/logic/{}", file), context)
+}
+
+fn render_lists(file: &str, context: &Context) -> Result<String, velto::Error> {
+    render_with_context(&format!("../tests/templates/logic/{}", file), context)
+}
+
+fn lines(file: &str, context: &HashMap<&str, &str>) -> Vec<String> {
+    render_logic(file, context)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_if_elif_else() {
+    let context = HashMap::from([("user", "Ada"), ("count", "12"), ("role", "admin")]);
+    assert_eq!(lines("if.html", &context), ["Hi Ada", "many", "admin no-x"]);
+
+    let mut context = Context::new();
+    context.insert("guest", "yes");
+    context.insert("count", 3);
+    context.insert("roles", vec!["admin", "x"]);
+    assert_eq!(
+        render_lists("if.html", &context).unwrap(),
+        "Welcome guest\nsome\nadmin\n"
+    );
+    context.insert("roles", vec!["administrator"]);
+    assert!(render_lists("if.html", &context)
+        .unwrap()
+        .ends_with("\n no-x\n"));
+
+    let mut context = HashMap::from([("guest", "yes"), ("banned", "yes"), ("count", "5")]);
+    assert_eq!(
+        lines("if.html", &context),
+        ["Please log in", "none", " no-x"]
+    );
+
+    context.insert("count", "1");
+    assert_eq!(lines("if.html", &context)[1], "one");
+}
+
+#[test]
+fn test_for_loop() {
+    let mut context = Context::new();
+    context.insert("items", vec!["<a>", "b", "c"]);
+    assert_eq!(
+        render_lists("for.html", &context).unwrap(),
+        "<ul><li class=\"first\">1/3 &lt;a&gt;</li><li class=\"\">2/3 b</li>\
+         <li class=\"last\">3/3 c</li></ul>\n"
+    );
+
+    context.insert("items", Vec::<&str>::new());
+    assert_eq!(
+        render_lists("for.html", &context).unwrap(),
+        "<ul><li>No items</li></ul>\n"
+    );
+    assert_eq!(
+        render_logic("for.html", &HashMap::new()).unwrap(),
+        "<ul><li>No items</li></ul>\n"
+    );
+}
+
+#[test]
+fn test_looping_over_a_string_fails() {
+    let context = HashMap::from([("items", "a, b, c")]);
+    assert_eq!(
+        render_logic("for.html", &context).unwrap_err().to_string(),
+        "Template '../tests/templates/logic/for.html' line 1: cannot loop over a string"
+    );
+}
+
+#[test]
+fn test_nested_loops_and_comments() {
+    let mut context = Context::new();
+    context.insert("rows", vec!["a", "b"]);
+    context.insert("cols", vec![1, 2]);
+    assert_eq!(
+        render_lists("nested.html", &context).unwrap(),
+        "a10,a21;b10,b21\n"
+    );
+}
+
+#[test]
+fn test_inheritance_with_logic() {
+    let mut context = Context::new();
+    context.insert("items", vec!["x", "y"]);
+    assert_eq!(
+        render_lists("child.html", &context).unwrap(),
+        "<title>Items</title>\n<main>[x][y]</main>\n<footer>x, y</footer>\n\n"
+    );
+}
+
+#[test]
+fn test_syntax_errors() {
+    let context = HashMap::new();
+    let message = |file: &str| render_logic(file, &context).unwrap_err().to_string();
+    assert_eq!(
+        message("bad_expr.html"),
+        "Template '../tests/templates/logic/bad_expr.html' line 3: expected an expression"
+    );
+    assert_eq!(
+        message("unclosed.html"),
+        "Template '../tests/templates/logic/unclosed.html': missing {% endif %}"
+    );
+    assert_eq!(
+        message("stray.html"),
+        "Template '../tests/templates/logic/stray.html' line 1: unexpected {% endfor %}"
+    );
+    assert_eq!(
+        message("loop.html"),
+        "Template '../tests/templates/logic/loop.html' includes or extends itself"
+    );
+    assert_eq!(
+        message("scalar.html"),
+        "Template '../tests/templates/logic/scalar.html' line 1: cannot loop over a number"
+    );
+    assert_eq!(
+        message("bad_raw.html"),
+        "Template '../tests/templates/logic/bad_raw.html' line 3: expected an expression"
+    );
+    assert_eq!(
+        message("unclosed_raw.html"),
+        "Template '../tests/templates/logic/unclosed_raw.html' line 2: missing {% endraw %}"
+    );
+}
+
+#[test]
+fn test_raw_sections_are_text() {
+    let context = HashMap::from([("name", "Ada")]);
+    assert_eq!(
+        render_logic("raw.html", &context).unwrap(),
+        "{{ name }} {% if %}{# note #}\nAda\n{{\n"
+    );
+}
+
+#[test]
+fn test_missing_include_renders_message_inline() {
+    let file = "../tests/templates/logic/missing_include.html";
+    let inline = "<p>before</p><h1>Template 'no_such.html' not found</h1><p>after</p>\n";
+    assert_eq!(render_template(file, &HashMap::new()), inline);
+    assert_eq!(render!(file, {}).body(), inline);
+
+    let err = try_render_template(file, &HashMap::new()).unwrap_err();
+    assert_eq!(err.to_string(), "Template 'no_such.html' not found");
+    assert!(try_render!(file, {}).is_err());
+}
+
+#[derive(Serialize)]
+struct Address {
+    city: String,
+}
+
+#[derive(Serialize)]
+struct User {
+    name: String,
+    admin: bool,
+    address: Address,
+}
+
+#[derive(Serialize)]
+struct Product {
+    name: &'static str,
+    price: f64,
+    tags: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+enum Role {
+    Editor,
+}
+
+fn products() -> Vec<Product> {
+    vec![
+        Product {
+            name: "Lamp",
+            price: 40.5,
+            tags: vec!["home", "light"],
+        },
+        Product {
+            name: "Desk",
+            price: 120.0,
+            tags: vec![],
+        },
+    ]
+}
+
+#[test]
+fn test_structured_context_lookup() {
+    let user = User {
+        name: "Ada".into(),
+        admin: false,
+        address: Address {
+            city: "London".into(),
+        },
+    };
+    let mut context = Context::new();
+    context.insert("user", Value::from_serialize(&user).unwrap());
+    context.insert("items", Value::from_serialize(&products()).unwrap());
+    context.insert("pick", 1);
+    context.insert("role", Value::from_serialize(&Role::Editor).unwrap());
+
+    assert_eq!(
+        render_with_context("../tests/templates/logic/structured.html", &context).unwrap(),
+        "Ada lives in London.\n\
+         Lamp, Desk, Ada, Desk, \n\
+         Lamp=40.5 (home, light); Desk=120; \n\
+         member ||Editor\n"
+    );
+}
+
+#[test]
+fn test_context_from_serialize() {
+    #[derive(Serialize)]
+    struct Page {
+        title: &'static str,
+        items: Vec<Product>,
+        count: Option<u32>,
+    }
+
+    let page = Page {
+        title: "Shop",
+        items: products(),
+        count: None,
+    };
+    let context = Context::from_serialize(&page).unwrap();
+    assert_eq!(context.get("title"), Some(&Value::from("Shop")));
+    assert_eq!(context.get("count"), Some(&Value::Null));
+    let items = context.get("items").unwrap();
+    assert_eq!(
+        items.get(&Value::Int(0)).unwrap().get(&"price".into()),
+        Some(&Value::Float(40.5))
+    );
+
+    let err = Context::from_serialize(&vec![1, 2]).unwrap_err();
+    assert_eq!(err.status(), 500);
+    assert_eq!(
+        err.to_string(),
+        "Cannot use a list as a template context; expected a struct or map"
+    );
+
+    let keys = HashMap::from([(vec![1], "x")]);
+    assert_eq!(
+        Value::from_serialize(&keys).unwrap_err().to_string(),
+        "Cannot use value in a template: map keys must be strings or numbers"
+    );
+}
+
+#[test]
+fn test_render_macro_accepts_any_value() {
+    let name = String::from("Ada");
+    let res = render!("../tests/templates/logic/structured.html", {
+        "user" => Value::from(HashMap::from([("name", name.clone())])),
+        "items" => Value::from_serialize(&products()).unwrap(),
+        "pick" => 0,
+        "role" => &name,
+    });
+    assert!(res
+        .body()
+        .starts_with("Ada lives in .\nLamp, Desk, Ada, Lamp, \n"));
+
+    let mut extra = Context::new();
+    extra.insert("pick", -1);
+    let res = try_render!("../tests/templates/logic/structured.html", {
+        "items" => Value::from_serialize(&products()).unwrap(),
+    }, extra)
+    .unwrap();
+    assert!(res.body().contains(", , Desk, \n"));
+}
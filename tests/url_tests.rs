@@ -0,0 +1,133 @@
+use velto::test::TestRequest;
+use velto::url::UrlError;
+use velto::{render, route, try_render, App, Response};
+
+fn app() -> App {
+    let mut app = App::new();
+    route!(app, "/users/:id/edit" as "user_edit" => |_req| Response::from_string("edit"));
+    route!(app, [GET, POST] "/files/*path" as "file" => |_req| Response::from_string("file"));
+    route!(app, "/" as "home" => |req| {
+        Response::from_string(req.url_for("user_edit", &[("id", "7")]).unwrap())
+    });
+    app
+}
+
+#[test]
+fn test_url_for_fills_params() {
+    let app = app();
+    assert_eq!(
+        app.url_for("user_edit", &[("id", "42")]).unwrap(),
+        "/users/42/edit"
+    );
+    assert_eq!(
+        app.url_for("file", &[("path", "a b/c.txt")]).unwrap(),
+        "/files/a%20b/c.txt"
+    );
+    assert_eq!(app.url_for("home", &[("page", "2")]).unwrap(), "/?page=2");
+}
+
+#[test]
+fn test_url_for_errors() {
+    let app = app();
+    assert_eq!(
+        app.url_for("nope", &[]),
+        Err(UrlError::UnknownRoute("nope".to_string()))
+    );
+    assert_eq!(
+        app.url_for("user_edit", &[]),
+        Err(UrlError::MissingParam {
+            route: "user_edit".to_string(),
+            param: "id".to_string(),
+        })
+    );
+}
+
+#[test]
+fn test_request_url_for() {
+    assert_eq!(
+        TestRequest::new("GET", "/").send(&app()).body(),
+        "/users/7/edit"
+    );
+}
+
+#[test]
+fn test_names_follow_scopes_and_mounts() {
+    let mut admin = App::new();
+    admin.scope("/users", |users| {
+        route!(users, "/:id" => |_req| Response::from_string("user"));
+        users.name("admin_user", "/:id");
+    });
+
+    let mut app = App::new();
+    app.mount("/admin", admin);
+    assert_eq!(
+        app.url_for("admin_user", &[("id", "1")]).unwrap(),
+        "/admin/users/1"
+    );
+}
+
+#[test]
+#[should_panic(expected = "already registered")]
+fn test_duplicate_name_panics() {
+    let mut app = app();
+    route!(app, "/other" as "home" => |_req| Response::from_string("other"));
+}
+
+#[test]
+#[should_panic(expected = "no route is registered at '/missing'")]
+fn test_naming_an_unknown_path_panics() {
+    let mut app = app();
+    app.name("missing", "/missing");
+}
+
+#[test]
+fn test_url_for_in_templates() {
+    let mut app = app();
+    route!(app, "/links" => |_req| render!("../tests/templates/links.html", {
+        "user_id" => "42"
+    }));
+
+    let body = TestRequest::new("GET", "/links").send(&app).body();
+    assert!(body.contains(r#"<a href="/users/42/edit">"#));
+    assert!(body.contains(r#"<a href="/files/docs/read%20me.txt">"#));
+}
+
+#[test]
+fn test_url_for_errors_fail_rendering() {
+    let mut app = app();
+    route!(app, "/broken" => |_req| {
+        match try_render!("../tests/templates/broken/missing.html", { "user_id" => "42" }) {
+            Ok(_) => Response::from_string("rendered"),
+            Err(err) => Response::from_string(err.to_string()),
+        }
+    });
+    route!(app, "/delete" => |_req| render!("../tests/templates/broken/missing.html", {}));
+
+    assert_eq!(
+        TestRequest::new("GET", "/broken").send(&app).body(),
+        "Template '../tests/templates/broken/missing.html' line 1: \
+         url_for: no route named 'user_delete'"
+    );
+    // `render!` shows the error in place of the page, as for other errors.
+    let body = TestRequest::new("GET", "/delete").send(&app).body();
+    assert!(
+        body.contains("url_for: no route named 'user_delete'"),
+        "{}",
+        body
+    );
+}
+
+#[test]
+fn test_check_templates() {
+    let app = app();
+    assert_eq!(app.check_templates("tests/templates/links.html"), Ok(()));
+
+    let err = app.check_templates("tests/templates").unwrap_err();
+    assert!(matches!(
+        err,
+        UrlError::InTemplate { ref error, .. }
+            if **error == UrlError::UnknownRoute("user_delete".to_string())
+    ));
+    assert!(err.to_string().contains("missing.html"));
+    assert_eq!(App::new().check_templates("does/not/exist"), Ok(()));
+}
@@ -0,0 +1,114 @@
+use velto::form::{self, FormData};
+use velto::test::TestRequest;
+use velto::validation::Validator;
+use velto::{render, route, App, Error, Request, Response};
+
+fn signup(req: &Request) -> Result<Response, Error> {
+    let form = FormData::try_parse(req.body())?;
+    Validator::new(&form)
+        .required("username")
+        .min_length("username", 3)
+        .email("email")
+        .matches("confirm", "password")
+        .sensitive("password")
+        .sensitive("confirm")
+        .validate()
+        .map(|()| Response::from_string("welcome"))
+        .or_else(|errors| {
+            Ok(
+                render!("signup.html", { "title" => "Sign Up" }, errors.context())
+                    .with_status_code(422),
+            )
+        })
+}
+
+#[test]
+fn test_rules() {
+    let form = form::parse("name=  &short=ab&long=abcdef&email=a@b.co&bad=a@b&optional=");
+    let errors = Validator::new(&form)
+        .required("name")
+        .required("missing")
+        .min_length("short", 3)
+        .max_length("long", 5)
+        .email("email")
+        .email("bad")
+        .min_length("optional", 3)
+        .email("optional")
+        .validate()
+        .unwrap_err();
+
+    let fields: Vec<&str> = errors.iter().map(|(field, _)| field).collect();
+    assert_eq!(fields, ["name", "missing", "short", "long", "bad"]);
+    assert_eq!(errors.get("name"), Some("This field is required"));
+    assert_eq!(errors.get("long"), Some("Must be at most 5 characters"));
+    assert_eq!(errors.get("email"), None);
+    assert_eq!(errors.len(), 5);
+}
+
+#[test]
+fn test_first_error_per_field_wins() {
+    let form = form::parse("username=");
+    let errors = Validator::new(&form)
+        .required("username")
+        .rule("username", |_| false, "Already taken")
+        .validate()
+        .unwrap_err();
+    assert_eq!(errors.get("username"), Some("This field is required"));
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn test_custom_rules_and_success() {
+    let form = FormData::parse("age=17&nick=ferris");
+    let is_adult = |value: &str| value.parse::<u8>().is_ok_and(|age| age >= 18);
+    let errors = Validator::new(&form)
+        .rule("age", is_adult, "Must be 18 or older")
+        .validate()
+        .unwrap_err();
+    assert_eq!(errors.to_string(), "age: Must be 18 or older");
+    assert_eq!(Error::from(errors).status(), 422);
+
+    assert!(Validator::new(&form)
+        .required("nick")
+        .min_length("nick", 3)
+        .validate()
+        .is_ok());
+}
+
+#[test]
+fn test_matches() {
+    let check = |body: &str| {
+        let form = form::parse(body);
+        Validator::new(&form)
+            .matches("confirm", "password")
+            .validate()
+            .is_ok()
+    };
+    assert!(check("password=secret&confirm=secret"));
+    assert!(check(""));
+    assert!(!check("password=secret&confirm=other"));
+    assert!(!check("password=secret"));
+}
+
+#[test]
+fn test_redisplay_with_render() {
+    let mut app = App::new();
+    route!(app, POST "/signup" => signup);
+
+    let res = TestRequest::new("POST", "/signup")
+        .with_body("username=al&email=al.example.com&password=hunter22&confirm=hunter2")
+        .send(&app);
+    assert_eq!(res.status_code(), 422);
+    let body = res.body();
+    assert!(body.contains(r#"value="al""#), "{}", body);
+    assert!(body.contains(r#"value="al.example.com""#), "{}", body);
+    assert!(body.contains("Must be at least 3 characters"), "{}", body);
+    assert!(body.contains("Must be a valid email address"), "{}", body);
+    assert!(body.contains("Must match password"), "{}", body);
+    assert!(!body.contains("hunter"), "{}", body);
+
+    let res = TestRequest::new("POST", "/signup")
+        .with_body("username=alice&email=alice@example.com&password=pw&confirm=pw")
+        .send(&app);
+    assert_eq!(res.body(), "welcome");
+}